@@ -23,6 +23,9 @@ where
     type WriteHandle = NopWriteHandle<K, V, S>;
     type ReadHandle = Self;
 
+    // Nothing is ever written
+    const ATOMIC_PUBLISH: bool = true;
+
     fn new(inner: HashMap<K, V, S>) -> (Self::WriteHandle, Self::ReadHandle) {
         let me = Self {
             inner: Arc::new(inner)
@@ -64,10 +67,28 @@ where
         self.inner.len()
     }
 
+    fn contains_key(&self, key: &K) -> bool {
+        self.inner.contains_key(key)
+    }
+
     fn get_and_test<F>(&self, key: &K, test: F) -> Option<bool>
     where
         F: FnOnce(&V) -> bool
     {
         self.inner.get(key).map(test)
     }
+
+    fn get_cloned(&self, key: &K) -> Option<V>
+    where
+        V: Clone
+    {
+        self.inner.get(key).cloned()
+    }
+
+    fn fold<B, F>(&self, init: B, mut f: F) -> B
+    where
+        F: FnMut(B, &K, &V) -> B
+    {
+        self.inner.iter().fold(init, |acc, (key, value)| f(acc, key, value))
+    }
 }
\ No newline at end of file
@@ -0,0 +1,374 @@
+//! Checks that every adapter implements the contract in `api.rs` the same way. Each adapter gets
+//! its own module of tests through `conformance_tests!`; read-only adapters skip the write tests.
+
+use std::{collections::{hash_map::RandomState, HashMap}, sync::{Arc, atomic::{AtomicBool, Ordering}}, thread};
+
+use crate::api::{ConcurrentMap, ReadHandle, ReadGuard, WriteHandle, WriteGuard, WriteOperation};
+use super::{ArcHashMap, DashMap, EvMap, FlashMap, FlurryMap};
+
+fn populated(n: u64) -> HashMap<u64, u64, RandomState> {
+    (0 .. n).map(|key| (key, key * 2)).collect()
+}
+
+fn value_of<R: ReadHandle<u64, u64, RandomState>>(read: &R, key: u64) -> Option<u64> {
+    let mut value = None;
+    read.guard().get_and_test(&key, |&v| {
+        value = Some(v);
+        true
+    });
+    value
+}
+
+fn new_keeps_entries<M: ConcurrentMap<u64, u64, RandomState>>() {
+    let (_write, read) = M::new(populated(100));
+
+    assert_eq!(read.guard().len(), 100);
+    for key in 0 .. 100 {
+        assert_eq!(value_of(&read, key), Some(key * 2));
+    }
+    assert_eq!(value_of(&read, 100), None);
+}
+
+fn with_capacity_is_empty<M: ConcurrentMap<u64, u64, RandomState>>() {
+    let (_write, read) = M::with_capacity(64, RandomState::new());
+
+    assert_eq!(read.guard().len(), 0);
+    assert_eq!(value_of(&read, 0), None);
+}
+
+fn read_methods_agree<M: ConcurrentMap<u64, u64, RandomState>>() {
+    let (_write, read) = M::new(populated(100));
+    let guard = read.guard();
+
+    assert!(guard.contains_key(&7));
+    assert!(!guard.contains_key(&100));
+    assert_eq!(guard.get_cloned(&7), Some(14));
+    assert_eq!(guard.get_cloned(&100), None);
+
+    let mut found = Vec::new();
+    guard.get_many(&[1, 100, 2], |&key, value| found.push((key, value.copied())));
+    assert_eq!(found, [(1, Some(2)), (100, None), (2, Some(4))]);
+
+    let (count, sum) = guard.fold((0, 0), |(count, sum), &key, &value| {
+        assert_eq!(value, key * 2);
+        (count + 1, sum + value)
+    });
+    assert_eq!(count, 100);
+    assert_eq!(sum, (0 .. 100).map(|key| key * 2).sum());
+}
+
+fn read_handle_clones_across_threads<M: ConcurrentMap<u64, u64, RandomState>>() {
+    let (_write, read) = M::new(populated(100));
+
+    let join_handles = (0 .. 4).map(|i| thread::spawn({
+        let read = read.clone();
+
+        move || {
+            for key in (i * 25) .. ((i + 1) * 25) {
+                assert_eq!(value_of(&read, key), Some(key * 2));
+            }
+            read.guard().len()
+        }
+    }))
+    .collect::<Vec<_>>();
+
+    for handle in join_handles {
+        assert_eq!(handle.join().unwrap(), 100);
+    }
+}
+
+fn writes_visible_after_guard_drop<M: ConcurrentMap<u64, u64, RandomState>>() {
+    let (mut write, read) = M::with_capacity(0, RandomState::new());
+
+    let mut guard = write.guard();
+    for key in 0 .. 10 {
+        guard.insert(key, key * 2);
+    }
+    drop(guard);
+
+    assert_eq!(read.guard().len(), 10);
+    for key in 0 .. 10 {
+        assert_eq!(value_of(&read, key), Some(key * 2));
+    }
+}
+
+fn writes_visible_on_other_threads<M: ConcurrentMap<u64, u64, RandomState>>() {
+    let (mut write, read) = M::with_capacity(0, RandomState::new());
+
+    let reader = thread::spawn({
+        let read = read.clone();
+        move || read
+    })
+    .join()
+    .unwrap();
+
+    write.guard().insert(1, 2);
+
+    let value = thread::spawn(move || value_of(&reader, 1)).join().unwrap();
+    assert_eq!(value, Some(2));
+}
+
+fn insert_return_values<M: ConcurrentMap<u64, u64, RandomState>>() {
+    let (mut write, read) = M::new(populated(10));
+
+    let mut guard = write.guard();
+    assert!(guard.insert(10, 20));
+    assert!(!guard.insert(5, 11));
+    drop(guard);
+
+    assert_eq!(read.guard().len(), 11);
+    assert_eq!(value_of(&read, 10), Some(20));
+    assert_eq!(value_of(&read, 5), Some(11));
+}
+
+fn remove_return_values<M: ConcurrentMap<u64, u64, RandomState>>() {
+    let (mut write, read) = M::new(populated(10));
+
+    let mut guard = write.guard();
+    assert!(guard.remove(3));
+    assert!(!guard.remove(3));
+    assert!(!guard.remove(10));
+    drop(guard);
+
+    assert_eq!(read.guard().len(), 9);
+    assert_eq!(value_of(&read, 3), None);
+}
+
+fn update_return_values<M: ConcurrentMap<u64, u64, RandomState>>() {
+    let (mut write, read) = M::new(populated(10));
+
+    let mut guard = write.guard();
+    assert!(guard.update(4, 9));
+    assert!(!guard.update(10, 21));
+    drop(guard);
+
+    assert_eq!(read.guard().len(), 10);
+    assert_eq!(value_of(&read, 4), Some(9));
+    assert_eq!(value_of(&read, 10), None);
+}
+
+fn upsert_return_values<M: ConcurrentMap<u64, u64, RandomState>>() {
+    let (mut write, read) = M::new(populated(10));
+
+    let mut guard = write.guard();
+    assert!(!guard.upsert(4, |value| value.unwrap() + 1));
+    assert!(guard.upsert(10, |value| {
+        assert!(value.is_none());
+        21
+    }));
+    drop(guard);
+
+    assert_eq!(read.guard().len(), 11);
+    assert_eq!(value_of(&read, 4), Some(9));
+    assert_eq!(value_of(&read, 10), Some(21));
+}
+
+fn compare_and_swap_return_values<M: ConcurrentMap<u64, u64, RandomState>>() {
+    let (mut write, read) = M::new(populated(10));
+
+    let mut guard = write.guard();
+    assert!(guard.compare_and_swap(4, &8, 9));
+    assert!(!guard.compare_and_swap(5, &8, 9));
+    assert!(!guard.compare_and_swap(10, &20, 21));
+    drop(guard);
+
+    assert_eq!(read.guard().len(), 10);
+    assert_eq!(value_of(&read, 4), Some(9));
+    assert_eq!(value_of(&read, 5), Some(10));
+    assert_eq!(value_of(&read, 10), None);
+}
+
+fn retain_and_clear<M: ConcurrentMap<u64, u64, RandomState>>() {
+    let (mut write, read) = M::new(populated(10));
+
+    write.guard().retain(|&key, _| key % 2 == 0);
+
+    assert_eq!(read.guard().len(), 5);
+    assert_eq!(value_of(&read, 4), Some(8));
+    assert_eq!(value_of(&read, 5), None);
+
+    write.guard().clear();
+
+    assert_eq!(read.guard().len(), 0);
+    assert_eq!(value_of(&read, 4), None);
+}
+
+fn apply_batch_in_order<M: ConcurrentMap<u64, u64, RandomState>>() {
+    let (mut write, read) = M::new(populated(10));
+
+    write.guard().apply_batch([
+        WriteOperation::Insert(10, 20),
+        WriteOperation::Update(10, 21),
+        WriteOperation::Remove(3),
+        WriteOperation::Upsert(4, 0, |&value| value + 1),
+        WriteOperation::Upsert(11, 22, |&value| value + 1),
+        WriteOperation::CompareAndSwap(5, 10, 11),
+        WriteOperation::Retain(|&key, _| key != 6),
+    ]);
+
+    assert_eq!(read.guard().len(), 10);
+    assert_eq!(value_of(&read, 10), Some(21));
+    assert_eq!(value_of(&read, 3), None);
+    assert_eq!(value_of(&read, 4), Some(9));
+    assert_eq!(value_of(&read, 11), Some(22));
+    assert_eq!(value_of(&read, 5), Some(11));
+    assert_eq!(value_of(&read, 6), None);
+
+    // Later operations see the keys earlier ones wrote
+    write.guard().apply_batch([
+        WriteOperation::Remove(7),
+        WriteOperation::Update(7, 1),
+        WriteOperation::Upsert(7, 4, |&value| value + 1),
+        WriteOperation::Upsert(7, 0, |&value| value + 1),
+        WriteOperation::CompareAndSwap(7, 5, 6),
+    ]);
+
+    assert_eq!(value_of(&read, 7), Some(6));
+
+    write.guard().apply_batch([WriteOperation::Clear, WriteOperation::Update(7, 1), WriteOperation::Insert(1, 2)]);
+
+    assert_eq!(read.guard().len(), 1);
+    assert_eq!(value_of(&read, 7), None);
+}
+
+// Every batch sets all keys to the batch number, so a reader seeing two different values under
+// one guard saw a partially applied batch. Upserts, compare-and-swaps and a retain half way
+// through need the values written earlier in the batch, without making them visible.
+fn batches_publish_atomically<M: ConcurrentMap<u64, u64, RandomState>>() {
+    const KEYS: u64 = 64;
+    const BATCHES: u64 = 1000;
+
+    if !M::ATOMIC_BATCHES {
+        return;
+    }
+
+    let (mut write, read) = M::new((0 .. KEYS).map(|key| (key, 0)).collect());
+    let writer_finished = Arc::new(AtomicBool::new(false));
+
+    let join_handles = (0 .. 2).map(|_| thread::spawn({
+        let read = read.clone();
+        let writer_finished = Arc::clone(&writer_finished);
+
+        move || {
+            let keys = (0 .. KEYS).collect::<Vec<_>>();
+
+            while !writer_finished.load(Ordering::Acquire) {
+                let mut seen = None;
+                read.guard().get_many(&keys, |_, value| {
+                    let value = *value.unwrap();
+                    assert_eq!(*seen.get_or_insert(value), value);
+                });
+            }
+        }
+    }))
+    .collect::<Vec<_>>();
+
+    for batch in 1 ..= BATCHES {
+        write.guard().apply_batch((0 .. KEYS / 2).map(|key| WriteOperation::Update(key, batch))
+            .chain([
+                WriteOperation::Upsert(0, batch, |&value| value),
+                WriteOperation::CompareAndSwap(1, batch, batch),
+                WriteOperation::Retain(|_, _| true),
+            ])
+            .chain((KEYS / 2 .. KEYS).map(|key| WriteOperation::Update(key, batch))));
+    }
+    writer_finished.store(true, Ordering::Release);
+
+    for handle in join_handles {
+        handle.join().unwrap();
+    }
+}
+
+macro_rules! conformance_tests {
+    (@read $map:ty) => {
+        #[test]
+        fn new_keeps_entries() {
+            super::new_keeps_entries::<$map>();
+        }
+
+        #[test]
+        fn with_capacity_is_empty() {
+            super::with_capacity_is_empty::<$map>();
+        }
+
+        #[test]
+        fn read_methods_agree() {
+            super::read_methods_agree::<$map>();
+        }
+
+        #[test]
+        fn read_handle_clones_across_threads() {
+            super::read_handle_clones_across_threads::<$map>();
+        }
+    };
+    ($name:ident, $map:ty, read_only) => {
+        mod $name {
+            use super::*;
+
+            conformance_tests!(@read $map);
+        }
+    };
+    ($name:ident, $map:ty) => {
+        mod $name {
+            use super::*;
+
+            conformance_tests!(@read $map);
+
+            #[test]
+            fn writes_visible_after_guard_drop() {
+                super::writes_visible_after_guard_drop::<$map>();
+            }
+
+            #[test]
+            fn writes_visible_on_other_threads() {
+                super::writes_visible_on_other_threads::<$map>();
+            }
+
+            #[test]
+            fn insert_return_values() {
+                super::insert_return_values::<$map>();
+            }
+
+            #[test]
+            fn remove_return_values() {
+                super::remove_return_values::<$map>();
+            }
+
+            #[test]
+            fn update_return_values() {
+                super::update_return_values::<$map>();
+            }
+
+            #[test]
+            fn upsert_return_values() {
+                super::upsert_return_values::<$map>();
+            }
+
+            #[test]
+            fn compare_and_swap_return_values() {
+                super::compare_and_swap_return_values::<$map>();
+            }
+
+            #[test]
+            fn retain_and_clear() {
+                super::retain_and_clear::<$map>();
+            }
+
+            #[test]
+            fn apply_batch_in_order() {
+                super::apply_batch_in_order::<$map>();
+            }
+
+            #[test]
+            fn batches_publish_atomically() {
+                super::batches_publish_atomically::<$map>();
+            }
+        }
+    };
+}
+
+conformance_tests!(arc_hash_map, ArcHashMap<u64, u64, RandomState>, read_only);
+conformance_tests!(dashmap, DashMap<u64, u64, RandomState>);
+conformance_tests!(evmap, EvMap);
+conformance_tests!(flashmap, FlashMap);
+conformance_tests!(flurry, FlurryMap<u64, u64, RandomState>);
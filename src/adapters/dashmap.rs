@@ -1,4 +1,5 @@
 use std::{hash::{Hash, BuildHasher}, collections::HashMap, sync::Arc};
+use dashmap::mapref::entry::Entry;
 use crate::api::{ConcurrentMap, ReadHandle, ReadGuard, WriteHandle, WriteGuard};
 
 pub struct DashMap<K, V, S> {
@@ -22,6 +23,8 @@ where
     type WriteHandle = Self;
     type ReadHandle = Self;
 
+    const ATOMIC_PUBLISH: bool = false;
+
     fn new(inner: HashMap<K, V, S>) -> (Self::WriteHandle, Self::ReadHandle) {
         let mut map = dashmap::DashMap::with_capacity_and_hasher(inner.len(), inner.hasher().clone());
         map.extend(inner);
@@ -71,6 +74,47 @@ where
             .map(|mut slot| *slot = value)
             .is_some()
     }
+
+    fn upsert<F>(&mut self, key: K, f: F) -> bool
+    where
+        F: FnOnce(Option<&V>) -> V
+    {
+        match self.inner.entry(key) {
+            Entry::Occupied(mut entry) => {
+                let value = f(Some(entry.get()));
+                entry.insert(value);
+                false
+            },
+            Entry::Vacant(entry) => {
+                entry.insert(f(None));
+                true
+            }
+        }
+    }
+
+    fn compare_and_swap(&mut self, key: K, current: &V, new: V) -> bool
+    where
+        V: PartialEq
+    {
+        match self.inner.get_mut(&key) {
+            Some(mut slot) if *slot == *current => {
+                *slot = new;
+                true
+            },
+            _ => false
+        }
+    }
+
+    fn retain<F>(&mut self, mut predicate: F)
+    where
+        F: FnMut(&K, &V) -> bool
+    {
+        self.inner.retain(|key, value| predicate(key, value));
+    }
+
+    fn clear(&mut self) {
+        self.inner.clear();
+    }
 }
 
 impl<K, V, S> ReadHandle<K, V, S> for DashMap<K, V, S>
@@ -98,6 +142,24 @@ where
         self.inner.get(key).as_deref().map(test)
     }
 
+    fn get_cloned(&self, key: &K) -> Option<V>
+    where
+        V: Clone
+    {
+        self.inner.get(key).as_deref().cloned()
+    }
+
+    fn contains_key(&self, key: &K) -> bool {
+        self.inner.contains_key(key)
+    }
+
+    fn fold<B, F>(&self, init: B, mut f: F) -> B
+    where
+        F: FnMut(B, &K, &V) -> B
+    {
+        self.inner.iter().fold(init, |acc, entry| f(acc, entry.key(), entry.value()))
+    }
+
     fn len(&self) -> usize {
         self.inner.len()
     }
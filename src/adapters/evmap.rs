@@ -2,7 +2,7 @@ use std::collections::HashMap;
 use std::hash::{Hash, BuildHasher};
 use evmap::refs::MapReadRef;
 
-use crate::api::{ConcurrentMap, WriteHandle, WriteGuard, ReadHandle, ReadGuard};
+use crate::api::{ConcurrentMap, WriteHandle, WriteGuard, WriteOperation, ReadHandle, ReadGuard};
 
 type EvWriteHandle<K, V, S> = evmap::handles::WriteHandle<K, V, (), S>;
 type EvReadHandle<K, V, S> = evmap::handles::ReadHandle<K, V, (), S>;
@@ -21,6 +21,10 @@ where
     type WriteHandle = EvWriteHandle<K, V, S>;
     type ReadHandle = EvReadHandle<K, V, S>;
 
+    // The write guard publishes after every write, except within a batch, see below
+    const ATOMIC_PUBLISH: bool = false;
+    const ATOMIC_BATCHES: bool = true;
+
     fn new(inner: HashMap<K, V, S>) -> (Self::WriteHandle, Self::ReadHandle) {
         let (mut write, read) = Self::with_capacity(inner.capacity(), inner.hasher().clone());
 
@@ -33,12 +37,17 @@ where
     }
 
     fn with_capacity(capacity: usize, hasher: S) -> (Self::WriteHandle, Self::ReadHandle) {
-        unsafe {
+        let (mut write, read) = unsafe {
             evmap::Options::default()
                 .with_capacity(capacity)
                 .with_hasher(hasher)
                 .assert_stable()
-        }
+        };
+
+        // Readers can't enter the map until the first publish
+        write.publish();
+
+        (write, read)
     }
 }
 
@@ -56,7 +65,9 @@ where
     }
 }
 
-// TODO: reconcile API differences
+// evmap stores a bag of values per key, so every write goes through `update`
+// (which replaces the bag) and presence is checked against the published map
+// to match the return values of the other adapters.
 impl<'a, K, V, S> WriteGuard<K, V, S> for &'a mut EvWriteHandle<K, V, S>
 where
     K: Eq + Hash + Clone,
@@ -64,19 +75,191 @@ where
     S: BuildHasher + Clone,
 {
     fn insert(&mut self, key: K, value: V) -> bool {
-        EvWriteHandle::insert(self, key, value).publish();
-        true
+        let inserted = !EvReadHandle::contains_key(self, &key);
+        EvWriteHandle::update(self, key, value).publish();
+        inserted
     }
 
     fn remove(&mut self, key: K) -> bool {
+        if !EvReadHandle::contains_key(self, &key) {
+            return false;
+        }
+
         EvWriteHandle::remove_entry(self, key).publish();
         true
     }
 
     fn update(&mut self, key: K, value: V) -> bool {
+        if !EvReadHandle::contains_key(self, &key) {
+            return false;
+        }
+
         EvWriteHandle::update(self, key, value).publish();
         true
     }
+
+    fn upsert<F>(&mut self, key: K, f: F) -> bool
+    where
+        F: FnOnce(Option<&V>) -> V
+    {
+        let (inserted, value) = match EvReadHandle::get_one(self, &key) {
+            Some(current) => (false, f(Some(&*current))),
+            None => (true, f(None))
+        };
+
+        EvWriteHandle::update(self, key, value).publish();
+        inserted
+    }
+
+    fn compare_and_swap(&mut self, key: K, current: &V, new: V) -> bool
+    where
+        V: PartialEq
+    {
+        let matches = EvReadHandle::get_one(self, &key)
+            .is_some_and(|value| *value == *current);
+
+        if matches {
+            EvWriteHandle::update(self, key, new).publish();
+        }
+
+        matches
+    }
+
+    // evmap's own `retain` filters the value-bag of a single key, so drop whole entries instead
+    fn retain<F>(&mut self, predicate: F)
+    where
+        F: FnMut(&K, &V) -> bool
+    {
+        for key in unretained(self, predicate) {
+            EvWriteHandle::remove_entry(self, key);
+        }
+        EvWriteHandle::publish(self);
+    }
+
+    // evmap's `clear` empties the value-bag of a single key, `purge` empties the map
+    fn clear(&mut self) {
+        EvWriteHandle::purge(self).publish();
+    }
+
+    // Publishes once for the whole batch. Lookups only see what has been published, so the batch
+    // is applied to an overlay of the keys it writes, which later operations read through, and
+    // the overlay is only written to the map at the end.
+    fn apply_batch(&mut self, operations: impl IntoIterator<Item = WriteOperation<K, V>>)
+    where
+        V: PartialEq
+    {
+        // The new value of each key written so far, `None` once removed
+        let mut pending = HashMap::<K, Option<V>>::new();
+        // Whether the map was cleared, which hides every published key not in `pending`
+        let mut cleared = false;
+
+        for operation in operations {
+            match operation {
+                WriteOperation::Insert(key, value) => {
+                    pending.insert(key, Some(value));
+                },
+                WriteOperation::Update(key, value) => {
+                    if current(self, &pending, cleared, &key).is_some() {
+                        pending.insert(key, Some(value));
+                    }
+                },
+                WriteOperation::Remove(key) => {
+                    if current(self, &pending, cleared, &key).is_some() {
+                        pending.insert(key, None);
+                    }
+                },
+                WriteOperation::Upsert(key, value, f) => {
+                    let value = current(self, &pending, cleared, &key).map_or(value, |current| f(&current));
+                    pending.insert(key, Some(value));
+                },
+                WriteOperation::CompareAndSwap(key, expected, new) => {
+                    if current(self, &pending, cleared, &key).is_some_and(|value| *value == expected) {
+                        pending.insert(key, Some(new));
+                    }
+                },
+                WriteOperation::Retain(predicate) => {
+                    let mut removed = pending.iter()
+                        .filter(|(key, value)| value.as_ref().is_some_and(|value| !predicate(key, value)))
+                        .map(|(key, _)| key.clone())
+                        .collect::<Vec<_>>();
+                    if !cleared {
+                        removed.extend(unretained(self, predicate).into_iter().filter(|key| !pending.contains_key(key)));
+                    }
+
+                    for key in removed {
+                        pending.insert(key, None);
+                    }
+                },
+                WriteOperation::Clear => {
+                    pending.clear();
+                    cleared = true;
+                },
+            }
+        }
+
+        if cleared {
+            EvWriteHandle::purge(self);
+        }
+        for (key, value) in pending {
+            match value {
+                Some(value) => EvWriteHandle::update(self, key, value),
+                None => EvWriteHandle::remove_entry(self, key),
+            };
+        }
+        EvWriteHandle::publish(self);
+    }
+}
+
+// The current value of `key` during `apply_batch`, through its overlay of pending writes
+fn current<'a, K, V, S>(
+    handle: &'a EvWriteHandle<K, V, S>,
+    pending: &'a HashMap<K, Option<V>>,
+    cleared: bool,
+    key: &K
+) -> Option<Current<'a, V>>
+where
+    K: Eq + Hash + Clone,
+    V: Eq + Hash,
+    S: BuildHasher + Clone,
+{
+    match pending.get(key) {
+        Some(value) => value.as_ref().map(Current::Pending),
+        None if cleared => None,
+        None => EvReadHandle::get_one(handle, key).map(Current::Published),
+    }
+}
+
+// A value seen through the overlay of `apply_batch`
+enum Current<'a, V> {
+    Pending(&'a V),
+    Published(evmap::refs::ReadGuard<'a, V>),
+}
+
+impl<V> std::ops::Deref for Current<'_, V> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        match self {
+            Self::Pending(value) => value,
+            Self::Published(value) => value,
+        }
+    }
+}
+
+// The published keys whose value `predicate` rejects
+fn unretained<K, V, S, F>(handle: &EvWriteHandle<K, V, S>, mut predicate: F) -> Vec<K>
+where
+    K: Eq + Hash + Clone,
+    V: Eq + Hash,
+    S: BuildHasher + Clone,
+    F: FnMut(&K, &V) -> bool
+{
+    EvReadHandle::enter(handle)
+        .map(|map| map.iter()
+            .filter(|(key, values)| values.get_one().is_some_and(|value| !predicate(key, value)))
+            .map(|(key, _)| key.clone())
+            .collect())
+        .unwrap_or_default()
 }
 
 impl<K, V, S> ReadHandle<K, V, S> for EvReadHandle<K, V, S>
@@ -106,6 +289,26 @@ where
         MapReadRef::get_one(self, key).map(test)
     }
 
+    fn get_cloned(&self, key: &K) -> Option<V>
+    where
+        V: Clone
+    {
+        MapReadRef::get_one(self, key).cloned()
+    }
+
+    fn contains_key(&self, key: &K) -> bool {
+        MapReadRef::contains_key(self, key)
+    }
+
+    fn fold<B, F>(&self, init: B, mut f: F) -> B
+    where
+        F: FnMut(B, &K, &V) -> B
+    {
+        MapReadRef::iter(self)
+            .filter_map(|(key, values)| values.get_one().map(|value| (key, value)))
+            .fold(init, |acc, (key, value)| f(acc, key, value))
+    }
+
     fn len(&self) -> usize {
         MapReadRef::len(self)
     }
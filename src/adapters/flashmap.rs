@@ -10,12 +10,15 @@ where
     Self: Send + 'static,
     flashmap::ReadHandle<K, V, S>: Send + 'static,
     flashmap::WriteHandle<K, V, S>: Send + 'static,
-    K: Eq + Hash,
+    K: Eq + Hash + Clone,
     S: BuildHasher + Clone
 {
     type WriteHandle = flashmap::WriteHandle<K, V, S>;
     type ReadHandle = flashmap::ReadHandle<K, V, S>;
 
+    // Changes are published when the write guard is dropped
+    const ATOMIC_PUBLISH: bool = true;
+
     fn new(inner: HashMap<K, V, S>) -> (Self::WriteHandle, Self::ReadHandle) {
         let (mut write, read) = Self::with_capacity(inner.capacity(), inner.hasher().clone());
 
@@ -39,7 +42,7 @@ where
 impl<K, V, S> WriteHandle<K, V, S> for flashmap::WriteHandle<K, V, S>
 where
     Self: Send + 'static,
-    K: Eq + Hash,
+    K: Eq + Hash + Clone,
     S: BuildHasher,
 {
     type Guard<'a> = flashmap::WriteGuard<'a, K, V, S>;
@@ -51,7 +54,7 @@ where
 
 impl<'a, K, V, S> WriteGuard<K, V, S> for flashmap::WriteGuard<'a, K, V, S>
 where
-    K: Eq + Hash,
+    K: Eq + Hash + Clone,
     S: BuildHasher,
 {
     fn insert(&mut self, key: K, value: V) -> bool {
@@ -65,6 +68,48 @@ where
     fn update(&mut self, key: K, value: V) -> bool {
         flashmap::WriteGuard::rcu(self, key, |_| value)
     }
+
+    fn upsert<F>(&mut self, key: K, f: F) -> bool
+    where
+        F: FnOnce(Option<&V>) -> V
+    {
+        if flashmap::WriteGuard::contains_key(self, &key) {
+            flashmap::WriteGuard::rcu(self, key, |value| f(Some(value)));
+            false
+        } else {
+            flashmap::WriteGuard::insert(self, key, f(None));
+            true
+        }
+    }
+
+    fn compare_and_swap(&mut self, key: K, current: &V, new: V) -> bool
+    where
+        V: PartialEq
+    {
+        if flashmap::WriteGuard::get(self, &key) != Some(current) {
+            return false;
+        }
+
+        flashmap::WriteGuard::rcu(self, key, |_| new)
+    }
+
+    fn retain<F>(&mut self, mut predicate: F)
+    where
+        F: FnMut(&K, &V) -> bool
+    {
+        let removed = flashmap::WriteGuard::iter(self)
+            .filter(|(key, value)| !predicate(key, value))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+
+        for key in removed {
+            flashmap::WriteGuard::remove(self, key);
+        }
+    }
+
+    fn clear(&mut self) {
+        WriteGuard::retain(self, |_, _| false);
+    }
 }
 
 impl<K, V, S> ReadHandle<K, V, S> for flashmap::ReadHandle<K, V, S>
@@ -92,6 +137,24 @@ where
         flashmap::ReadGuard::get(self, key).map(test)
     }
 
+    fn get_cloned(&self, key: &K) -> Option<V>
+    where
+        V: Clone
+    {
+        flashmap::ReadGuard::get(self, key).cloned()
+    }
+
+    fn contains_key(&self, key: &K) -> bool {
+        flashmap::ReadGuard::contains_key(self, key)
+    }
+
+    fn fold<B, F>(&self, init: B, mut f: F) -> B
+    where
+        F: FnMut(B, &K, &V) -> B
+    {
+        flashmap::ReadGuard::iter(self).fold(init, |acc, (key, value)| f(acc, key, value))
+    }
+
     fn len(&self) -> usize {
         flashmap::ReadGuard::len(self)
     }
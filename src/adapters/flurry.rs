@@ -23,6 +23,8 @@ where
     type WriteHandle = Self;
     type ReadHandle = Self;
 
+    const ATOMIC_PUBLISH: bool = false;
+
     fn new(inner: HashMap<K, V, S>) -> (Self::WriteHandle, Self::ReadHandle) {
         let (mut write, read) = Self::with_capacity(inner.capacity(), inner.hasher().clone());
 
@@ -83,6 +85,49 @@ where
     fn update(&mut self, key: K, value: V) -> bool {
         self.map.compute_if_present(&key, |_, _| Some(value), &self.guard).is_some()
     }
+
+    fn upsert<F>(&mut self, key: K, f: F) -> bool
+    where
+        F: FnOnce(Option<&V>) -> V
+    {
+        // flurry has no `compute`, so fall back to an insert when the key is absent
+        let mut f = Some(f);
+        let updated = self.map.compute_if_present(
+            &key,
+            |_, value| Some((f.take().unwrap())(Some(value))),
+            &self.guard
+        );
+
+        if updated.is_some() {
+            return false;
+        }
+
+        self.map.insert(key, (f.take().unwrap())(None), &self.guard);
+        true
+    }
+
+    fn compare_and_swap(&mut self, key: K, current: &V, new: V) -> bool
+    where
+        V: PartialEq
+    {
+        // Returning `None` from `compute_if_present` removes the entry, so test before swapping
+        if self.map.get(&key, &self.guard) != Some(current) {
+            return false;
+        }
+
+        self.map.compute_if_present(&key, |_, _| Some(new), &self.guard).is_some()
+    }
+
+    fn retain<F>(&mut self, predicate: F)
+    where
+        F: FnMut(&K, &V) -> bool
+    {
+        self.map.retain(predicate, &self.guard);
+    }
+
+    fn clear(&mut self) {
+        self.map.clear(&self.guard);
+    }
 }
 
 impl<K, V, S> ReadHandle<K, V, S> for FlurryMap<K, V, S>
@@ -118,6 +163,24 @@ where
         self.map.get(key, &self.guard).map(test)
     }
 
+    fn get_cloned(&self, key: &K) -> Option<V>
+    where
+        V: Clone
+    {
+        self.map.get(key, &self.guard).cloned()
+    }
+
+    fn contains_key(&self, key: &K) -> bool {
+        self.map.contains_key(key, &self.guard)
+    }
+
+    fn fold<B, F>(&self, init: B, mut f: F) -> B
+    where
+        F: FnMut(B, &K, &V) -> B
+    {
+        self.map.iter(&self.guard).fold(init, |acc, (key, value)| f(acc, key, value))
+    }
+
     fn len(&self) -> usize {
         self.map.len()
     }
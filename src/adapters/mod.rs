@@ -3,9 +3,14 @@ mod dashmap;
 mod evmap;
 mod flashmap;
 mod flurry;
+mod recording;
 
 pub use arc_map::ArcHashMap;
 pub use self::dashmap::DashMap;
 pub use self::evmap::EvMap;
 pub use self::flashmap::FlashMap;
 pub use self::flurry::FlurryMap;
+pub use recording::Recording;
+
+#[cfg(test)]
+mod conformance;
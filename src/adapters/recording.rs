@@ -0,0 +1,222 @@
+use std::{cell::RefCell, collections::HashMap, marker::PhantomData, mem, sync::Arc, time::Instant};
+
+use crate::{
+    api::{ConcurrentMap, ReadHandle, ReadGuard, WriteHandle, WriteGuard},
+    trace::{self, TraceEvent, TraceOp},
+    types::BenchKey,
+};
+
+/// Wraps another adapter, recording every lookup and write as a trace event. Events are recorded
+/// under the [`trace::current_thread`] of the thread doing them, so clients sharing a write handle
+/// stay apart and handles that are never used leave nothing behind. Events are handed to
+/// [`trace::take_recorded`] when their handle is dropped.
+///
+/// `fold` and `len` aren't recorded, nor are `retain` and `clear`, which the trace format can't
+/// express. Upserts are recorded as the insert or update they turned out to be, and failed
+/// compare-and-swaps as reads.
+pub struct Recording<M> {
+    _marker: PhantomData<M>,
+}
+
+struct EventLog {
+    // When the map was created, shared by all of its handles
+    start: Arc<Instant>,
+    events: RefCell<Vec<TraceEvent>>,
+}
+
+impl EventLog {
+    fn new(start: Arc<Instant>) -> Self {
+        Self {
+            start,
+            events: RefCell::new(Vec::new()),
+        }
+    }
+
+    fn record<K: BenchKey>(&self, op: TraceOp, key: &K) {
+        self.events.borrow_mut().push(TraceEvent {
+            thread: trace::current_thread(),
+            time: self.start.elapsed(),
+            op,
+            key: key.index(),
+        });
+    }
+}
+
+impl Drop for EventLog {
+    fn drop(&mut self) {
+        trace::record(&mut mem::take(self.events.get_mut()));
+    }
+}
+
+pub struct RecordingHandle<H> {
+    inner: H,
+    log: EventLog,
+}
+
+impl<H: Clone> Clone for RecordingHandle<H> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            log: EventLog::new(Arc::clone(&self.log.start)),
+        }
+    }
+}
+
+impl<K, V, S, M> ConcurrentMap<K, V, S> for Recording<M>
+where
+    M: ConcurrentMap<K, V, S>,
+    K: BenchKey,
+{
+    type WriteHandle = RecordingHandle<M::WriteHandle>;
+    type ReadHandle = RecordingHandle<M::ReadHandle>;
+
+    const ATOMIC_PUBLISH: bool = M::ATOMIC_PUBLISH;
+
+    fn new(inner: HashMap<K, V, S>) -> (Self::WriteHandle, Self::ReadHandle) {
+        wrap(M::new(inner))
+    }
+
+    fn with_capacity(capacity: usize, hasher: S) -> (Self::WriteHandle, Self::ReadHandle) {
+        wrap(M::with_capacity(capacity, hasher))
+    }
+}
+
+fn wrap<W, R>((write, read): (W, R)) -> (RecordingHandle<W>, RecordingHandle<R>) {
+    let start = Arc::new(Instant::now());
+
+    let write = RecordingHandle {
+        inner: write,
+        log: EventLog::new(Arc::clone(&start)),
+    };
+    let read = RecordingHandle {
+        inner: read,
+        log: EventLog::new(start),
+    };
+
+    (write, read)
+}
+
+impl<K, V, S, H> WriteHandle<K, V, S> for RecordingHandle<H>
+where
+    H: WriteHandle<K, V, S>,
+    K: BenchKey,
+{
+    type Guard<'a> = RecordingGuard<'a, H::Guard<'a>>;
+
+    fn guard(&mut self) -> Self::Guard<'_> {
+        RecordingGuard {
+            inner: self.inner.guard(),
+            log: &self.log,
+        }
+    }
+}
+
+impl<K, V, S, H> ReadHandle<K, V, S> for RecordingHandle<H>
+where
+    H: ReadHandle<K, V, S>,
+    K: BenchKey,
+{
+    type Guard<'a> = RecordingGuard<'a, H::Guard<'a>>;
+
+    fn guard(&self) -> Self::Guard<'_> {
+        RecordingGuard {
+            inner: self.inner.guard(),
+            log: &self.log,
+        }
+    }
+}
+
+pub struct RecordingGuard<'a, G> {
+    inner: G,
+    log: &'a EventLog,
+}
+
+impl<'a, K, V, S, G> WriteGuard<K, V, S> for RecordingGuard<'a, G>
+where
+    G: WriteGuard<K, V, S>,
+    K: BenchKey,
+{
+    fn insert(&mut self, key: K, value: V) -> bool {
+        self.log.record(TraceOp::Insert, &key);
+        self.inner.insert(key, value)
+    }
+
+    fn remove(&mut self, key: K) -> bool {
+        self.log.record(TraceOp::Remove, &key);
+        self.inner.remove(key)
+    }
+
+    fn update(&mut self, key: K, value: V) -> bool {
+        self.log.record(TraceOp::Update, &key);
+        self.inner.update(key, value)
+    }
+
+    fn upsert<F>(&mut self, key: K, f: F) -> bool
+    where
+        F: FnOnce(Option<&V>) -> V
+    {
+        let index = key.clone();
+        let inserted = self.inner.upsert(key, f);
+        self.log.record(if inserted { TraceOp::Insert } else { TraceOp::Update }, &index);
+        inserted
+    }
+
+    fn compare_and_swap(&mut self, key: K, current: &V, new: V) -> bool
+    where
+        V: PartialEq
+    {
+        let index = key.clone();
+        let swapped = self.inner.compare_and_swap(key, current, new);
+        self.log.record(if swapped { TraceOp::Update } else { TraceOp::Read }, &index);
+        swapped
+    }
+
+    fn retain<F>(&mut self, predicate: F)
+    where
+        F: FnMut(&K, &V) -> bool
+    {
+        self.inner.retain(predicate);
+    }
+
+    fn clear(&mut self) {
+        self.inner.clear();
+    }
+}
+
+impl<'a, K, V, S, G> ReadGuard<K, V, S> for RecordingGuard<'a, G>
+where
+    G: ReadGuard<K, V, S>,
+    K: BenchKey,
+{
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn contains_key(&self, key: &K) -> bool {
+        self.log.record(TraceOp::Read, key);
+        self.inner.contains_key(key)
+    }
+
+    fn get_and_test<F>(&self, key: &K, test: F) -> Option<bool>
+    where
+        F: FnOnce(&V) -> bool
+    {
+        self.log.record(TraceOp::Read, key);
+        self.inner.get_and_test(key, test)
+    }
+
+    fn get_cloned(&self, key: &K) -> Option<V>
+    where
+        V: Clone
+    {
+        self.log.record(TraceOp::Read, key);
+        self.inner.get_cloned(key)
+    }
+
+    fn fold<B, F>(&self, init: B, f: F) -> B
+    where
+        F: FnMut(B, &K, &V) -> B
+    {
+        self.inner.fold(init, f)
+    }
+}
@@ -4,6 +4,12 @@ pub trait ConcurrentMap<K, V, S> {
     type WriteHandle: WriteHandle<K, V, S>;
     type ReadHandle: ReadHandle<K, V, S>;
 
+    /// Whether everything written through one write guard becomes visible to readers at once.
+    const ATOMIC_PUBLISH: bool;
+
+    /// Whether a batch given to [`WriteGuard::apply_batch`] becomes visible to readers at once.
+    const ATOMIC_BATCHES: bool = Self::ATOMIC_PUBLISH;
+
     fn new(inner: HashMap<K, V, S>) -> (Self::WriteHandle, Self::ReadHandle);
 
     fn with_capacity(capacity: usize, hasher: S) -> (Self::WriteHandle, Self::ReadHandle);
@@ -15,12 +21,85 @@ pub trait WriteHandle<K, V, S>: Send + 'static {
     fn guard(&mut self) -> Self::Guard<'_>;
 }
 
+/// Writes may be buffered until the guard is dropped, at which point they must be visible to
+/// every read handle.
 pub trait WriteGuard<K, V, S> {
+    /// Inserts or replaces the value for `key`, returning `true` if the key was not present.
     fn insert(&mut self, key: K, value: V) -> bool;
 
+    /// Removes `key`, returning `true` if it was present.
     fn remove(&mut self, key: K) -> bool;
 
+    /// Replaces the value for `key` if it is present, returning `false` and leaving the map
+    /// untouched otherwise.
     fn update(&mut self, key: K, f: V) -> bool;
+
+    /// Sets the value for `key` to `f` applied to the current value, if any, returning `true`
+    /// if the key was not present.
+    fn upsert<F>(&mut self, key: K, f: F) -> bool
+    where
+        F: FnOnce(Option<&V>) -> V;
+
+    /// Replaces the value for `key` with `new` only if it currently equals `current`, returning
+    /// whether the swap happened.
+    fn compare_and_swap(&mut self, key: K, current: &V, new: V) -> bool
+    where
+        V: PartialEq;
+
+    /// Removes every entry for which `predicate` returns `false`.
+    fn retain<F>(&mut self, predicate: F)
+    where
+        F: FnMut(&K, &V) -> bool;
+
+    fn clear(&mut self);
+
+    fn apply(&mut self, operation: WriteOperation<K, V>)
+    where
+        V: PartialEq
+    {
+        match operation {
+            WriteOperation::Insert(key, value) => {
+                self.insert(key, value);
+            },
+            WriteOperation::Update(key, value) => {
+                self.update(key, value);
+            },
+            WriteOperation::Remove(key) => {
+                self.remove(key);
+            },
+            WriteOperation::Upsert(key, value, f) => {
+                self.upsert(key, |current| current.map_or(value, f));
+            },
+            WriteOperation::CompareAndSwap(key, current, new) => {
+                self.compare_and_swap(key, &current, new);
+            },
+            WriteOperation::Retain(predicate) => self.retain(predicate),
+            WriteOperation::Clear => self.clear(),
+        }
+    }
+
+    /// Applies `operations` in order. On maps with `ATOMIC_BATCHES` readers see either none or
+    /// all of the batch.
+    fn apply_batch(&mut self, operations: impl IntoIterator<Item = WriteOperation<K, V>>)
+    where
+        V: PartialEq
+    {
+        for operation in operations {
+            self.apply(operation);
+        }
+    }
+}
+
+pub enum WriteOperation<K, V> {
+    Insert(K, V),
+    Update(K, V),
+    Remove(K),
+    /// Inserts the value if the key is absent, otherwise replaces the current value with the
+    /// function applied to it
+    Upsert(K, V, fn(&V) -> V),
+    CompareAndSwap(K, V, V),
+    Retain(fn(&K, &V) -> bool),
+    Clear,
 }
 
 pub trait ReadHandle<K, V, S>: Send + Clone + 'static {
@@ -32,9 +111,34 @@ pub trait ReadHandle<K, V, S>: Send + Clone + 'static {
 pub trait ReadGuard<K, V, S> {
     fn len(&self) -> usize;
 
+    fn contains_key(&self, key: &K) -> bool;
+
     fn get_and_test<F>(&self, key: &K, test: F) -> Option<bool>
     where
         F: FnOnce(&V) -> bool;
+
+    fn get_cloned(&self, key: &K) -> Option<V>
+    where
+        V: Clone;
+
+    /// Looks up every key in `keys` under this guard, calling `f` with the value of each key or
+    /// `None` if it is missing. None of the underlying maps offer a native batched lookup, so
+    /// this is just a loop over `get_and_test` sharing the guard.
+    fn get_many<F>(&self, keys: &[K], mut f: F)
+    where
+        F: FnMut(&K, Option<&V>)
+    {
+        for key in keys {
+            if self.get_and_test(key, |value| { f(key, Some(value)); true }).is_none() {
+                f(key, None);
+            }
+        }
+    }
+
+    /// Folds over every entry visible to this guard, in no particular order.
+    fn fold<B, F>(&self, init: B, f: F) -> B
+    where
+        F: FnMut(B, &K, &V) -> B;
 }
 
 pub struct NopWriteHandle<K, V, S> {
@@ -80,4 +184,25 @@ impl<K, V, S> WriteGuard<K, V, S> for NopWriteHandle<K, V, S> {
     fn update(&mut self, _key: K, _value: V) -> bool {
         true
     }
+
+    fn upsert<F>(&mut self, _key: K, _f: F) -> bool
+    where
+        F: FnOnce(Option<&V>) -> V
+    {
+        true
+    }
+
+    fn compare_and_swap(&mut self, _key: K, _current: &V, _new: V) -> bool
+    where
+        V: PartialEq
+    {
+        true
+    }
+
+    fn retain<F>(&mut self, _predicate: F)
+    where
+        F: FnMut(&K, &V) -> bool
+    {}
+
+    fn clear(&mut self) {}
 }
@@ -0,0 +1,277 @@
+//! What each benchmark thread did, and how that adds up across threads.
+//!
+//! Threads don't start and finish together, and many run for a time rather than a number of
+//! operations, so their windows overlap only partly and hold different operation counts. Throughput
+//! is every operation over the wall-clock time at least one thread was running, and latency is the
+//! time threads spent over the operations they actually did, not counting time paced threads spent
+//! waiting for their next request to be due.
+
+use std::{fmt, time::{Duration, Instant}};
+
+use crate::memory::AllocationCount;
+
+#[derive(Clone, Copy)]
+pub struct ExecutionWindow {
+    pub start: Instant,
+    pub end: Instant,
+    pub operations: usize,
+    // Lookups that found their key
+    pub hits: usize,
+    pub allocations: AllocationCount,
+    // Time spent waiting for requests to be due, which is no operation's latency
+    pub idle: Duration,
+}
+
+impl ExecutionWindow {
+    pub fn time_elapsed(&self) -> Duration {
+        self.end - self.start
+    }
+
+    // Operations per second
+    pub fn throughput(&self) -> u64 {
+        rate(self.operations, self.time_elapsed())
+    }
+
+    pub fn mean_latency(&self) -> Duration {
+        per_operation(self.busy_time(), self.operations)
+    }
+
+    fn busy_time(&self) -> Duration {
+        self.time_elapsed().saturating_sub(self.idle)
+    }
+}
+
+/// The execution windows of several threads put together.
+pub struct Aggregate {
+    pub operations: usize,
+    pub hits: usize,
+    pub allocations: AllocationCount,
+    /// How long at least one window was open, not counting gaps between windows
+    pub wall_time: Duration,
+    /// The lengths of the windows added up, less the time they were idle
+    pub thread_time: Duration,
+    /// The throughput of each window on its own, in the order they were given
+    pub thread_throughputs: Vec<u64>,
+}
+
+impl Aggregate {
+    pub fn of<'a>(windows: impl IntoIterator<Item = &'a ExecutionWindow>) -> Self {
+        let windows = windows.into_iter().collect::<Vec<_>>();
+
+        let mut spans = windows.iter().map(|window| (window.start, window.end)).collect::<Vec<_>>();
+        spans.sort_unstable();
+
+        // Merge overlapping windows, timing each run of them once
+        let mut wall_time = Duration::ZERO;
+        let mut open: Option<(Instant, Instant)> = None;
+        for &(window_start, window_end) in &spans {
+            open = match open {
+                Some((start, end)) if window_start <= end => Some((start, end.max(window_end))),
+                Some((start, end)) => {
+                    wall_time += end - start;
+                    Some((window_start, window_end))
+                },
+                None => Some((window_start, window_end)),
+            };
+        }
+        if let Some((start, end)) = open {
+            wall_time += end - start;
+        }
+
+        Self {
+            operations: windows.iter().map(|window| window.operations).sum(),
+            hits: windows.iter().map(|window| window.hits).sum(),
+            allocations: windows.iter().map(|window| window.allocations).sum(),
+            wall_time,
+            thread_time: windows.iter().map(|window| window.busy_time()).sum(),
+            thread_throughputs: windows.iter().map(|window| window.throughput()).collect(),
+        }
+    }
+
+    // Operations per second of wall-clock time
+    pub fn throughput(&self) -> u64 {
+        rate(self.operations, self.wall_time)
+    }
+
+    /// The time a thread took per operation, on average over every operation.
+    pub fn mean_latency(&self) -> Duration {
+        per_operation(self.thread_time, self.operations)
+    }
+
+    pub fn fairness(&self) -> Fairness {
+        Fairness::of(&self.thread_throughputs)
+    }
+}
+
+fn rate(operations: usize, elapsed: Duration) -> u64 {
+    match elapsed.as_nanos() {
+        0 => 0,
+        nanos => u64::try_from(operations as u128 * 1_000_000_000 / nanos).unwrap(),
+    }
+}
+
+fn per_operation(elapsed: Duration, operations: usize) -> Duration {
+    match operations {
+        0 => Duration::ZERO,
+        operations => Duration::from_nanos((elapsed.as_nanos() / operations as u128) as u64),
+    }
+}
+
+/// How evenly threads shared the work, from their throughputs.
+pub struct Fairness {
+    /// The slowest thread's throughput over the fastest's
+    pub min_max: f64,
+    /// Standard deviation over mean
+    pub coefficient_of_variation: f64,
+    /// 1 when every thread did as well, down to 1/n when one thread did everything
+    pub jain_index: f64,
+}
+
+impl Fairness {
+    pub fn of(throughputs: &[u64]) -> Self {
+        let n = throughputs.len() as f64;
+        let min = throughputs.iter().copied().min().unwrap_or(0) as f64;
+        let max = throughputs.iter().copied().max().unwrap_or(0) as f64;
+        let sum = throughputs.iter().map(|&throughput| throughput as f64).sum::<f64>();
+        let sum_of_squares = throughputs.iter().map(|&throughput| (throughput as f64).powi(2)).sum::<f64>();
+
+        let mean = sum / n;
+        let variance = sum_of_squares / n - mean * mean;
+
+        Self {
+            min_max: if max > 0.0 { min / max } else { 1.0 },
+            coefficient_of_variation: if mean > 0.0 { variance.max(0.0).sqrt() / mean } else { 0.0 },
+            jain_index: if sum_of_squares > 0.0 { sum * sum / (n * sum_of_squares) } else { 1.0 },
+        }
+    }
+}
+
+impl fmt::Display for Fairness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Fairness: min/max {:.3}, CV {:.1}%, Jain's index {:.4}",
+            self.min_max,
+            self.coefficient_of_variation * 100.0,
+            self.jain_index
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A window from `start` to `end` milliseconds after `base`
+    fn window(base: Instant, start: u64, end: u64, operations: usize) -> ExecutionWindow {
+        ExecutionWindow {
+            start: base + Duration::from_millis(start),
+            end: base + Duration::from_millis(end),
+            operations,
+            hits: operations / 2,
+            allocations: AllocationCount::default(),
+            idle: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn overlapping_windows_share_wall_time() {
+        let base = Instant::now();
+        let aggregate = Aggregate::of(&[
+            window(base, 0, 2000, 200),
+            window(base, 1000, 3000, 100),
+        ]);
+
+        assert_eq!(aggregate.operations, 300);
+        assert_eq!(aggregate.hits, 150);
+        assert_eq!(aggregate.wall_time, Duration::from_secs(3));
+        assert_eq!(aggregate.thread_time, Duration::from_secs(4));
+        assert_eq!(aggregate.throughput(), 100);
+        assert_eq!(aggregate.thread_throughputs, [100, 50]);
+        // 4s of thread time over 300 operations, not over the operations either thread was
+        // expected to do
+        assert_eq!(aggregate.mean_latency(), Duration::from_nanos(13_333_333));
+    }
+
+    #[test]
+    fn nested_windows_count_once() {
+        let base = Instant::now();
+        let aggregate = Aggregate::of(&[
+            window(base, 0, 4000, 400),
+            window(base, 1000, 2000, 100),
+            window(base, 1500, 3000, 150),
+        ]);
+
+        assert_eq!(aggregate.wall_time, Duration::from_secs(4));
+        assert_eq!(aggregate.throughput(), 162);
+    }
+
+    #[test]
+    fn staggered_windows_skip_gaps() {
+        let base = Instant::now();
+        // Given out of order, with a second in between nothing was running
+        let aggregate = Aggregate::of(&[
+            window(base, 2000, 3000, 100),
+            window(base, 0, 1000, 100),
+            window(base, 3000, 3500, 100),
+        ]);
+
+        assert_eq!(aggregate.wall_time, Duration::from_millis(2500));
+        assert_eq!(aggregate.throughput(), 120);
+        assert_eq!(aggregate.thread_throughputs, [100, 100, 200]);
+        assert_eq!(aggregate.mean_latency(), Duration::from_nanos(8_333_333));
+    }
+
+    #[test]
+    fn no_windows() {
+        let aggregate = Aggregate::of(&[]);
+
+        assert_eq!(aggregate.operations, 0);
+        assert_eq!(aggregate.wall_time, Duration::ZERO);
+        assert_eq!(aggregate.throughput(), 0);
+        assert_eq!(aggregate.mean_latency(), Duration::ZERO);
+    }
+
+    #[test]
+    fn empty_window_has_no_throughput() {
+        let base = Instant::now();
+        let empty = window(base, 1000, 1000, 0);
+
+        assert_eq!(empty.throughput(), 0);
+        assert_eq!(empty.mean_latency(), Duration::ZERO);
+    }
+
+    #[test]
+    fn idle_time_is_not_latency() {
+        let base = Instant::now();
+        let paced = ExecutionWindow {
+            idle: Duration::from_millis(1500),
+            ..window(base, 0, 2000, 100)
+        };
+        let aggregate = Aggregate::of(&[paced]);
+
+        assert_eq!(paced.throughput(), 50);
+        assert_eq!(paced.mean_latency(), Duration::from_millis(5));
+        assert_eq!(aggregate.throughput(), 50);
+        assert_eq!(aggregate.thread_time, Duration::from_millis(500));
+        assert_eq!(aggregate.mean_latency(), Duration::from_millis(5));
+    }
+
+    #[test]
+    fn fairness_of_equal_threads() {
+        let fairness = Fairness::of(&[100, 100, 100, 100]);
+
+        assert_eq!(fairness.min_max, 1.0);
+        assert_eq!(fairness.coefficient_of_variation, 0.0);
+        assert_eq!(fairness.jain_index, 1.0);
+    }
+
+    #[test]
+    fn fairness_of_one_busy_thread() {
+        let fairness = Fairness::of(&[400, 0, 0, 0]);
+
+        assert_eq!(fairness.min_max, 0.0);
+        assert!((fairness.coefficient_of_variation - 3f64.sqrt()).abs() < 1e-9);
+        assert_eq!(fairness.jain_index, 0.25);
+    }
+}
@@ -0,0 +1,67 @@
+use std::hash::{BuildHasherDefault, Hasher};
+
+pub type SipHash = std::collections::hash_map::RandomState;
+pub type FxHash = fxhash::FxBuildHasher;
+pub type AHash = ahash::RandomState;
+pub type IdentityHash = BuildHasherDefault<IdentityHasher>;
+
+/// A deliberately poor hasher: integers hash to themselves and byte strings to their last eight
+/// bytes, so maps relying on the high bits or on well mixed hashes degrade. A `u8` counts as one
+/// more byte, so a `str`, whose `Hash` ends with a `0xff` byte, hashes to its last seven bytes
+/// followed by `0xff`.
+#[derive(Default)]
+pub struct IdentityHasher(u64);
+
+impl Hasher for IdentityHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 << 8) | u64::from(byte);
+        }
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.0 = i.into();
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.0 = i.into();
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.0 = i;
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.0 = i as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::BuildHasher;
+
+    use super::*;
+
+    #[test]
+    fn integers_hash_to_themselves() {
+        let hasher = IdentityHash::default();
+
+        assert_eq!(hasher.hash_one(0x0102_0304_0506_0708u64), 0x0102_0304_0506_0708);
+        assert_eq!(hasher.hash_one(0x0102_0304usize), 0x0102_0304);
+        assert_eq!(hasher.hash_one(0x0102_0304u32), 0x0102_0304);
+        assert_eq!(hasher.hash_one(0x0102u16), 0x0102);
+    }
+
+    #[test]
+    fn byte_strings_hash_to_their_last_eight_bytes() {
+        let hasher = IdentityHash::default();
+
+        // The length prefix is shifted out by the bytes
+        assert_eq!(hasher.hash_one([0xaa, 1, 2, 3, 4, 5, 6, 7, 8u8]), 0x0102_0304_0506_0708);
+        assert_eq!(hasher.hash_one("abcdefghij"), u64::from_be_bytes(*b"defghij\xff"));
+    }
+}
@@ -0,0 +1,281 @@
+//! Latency histograms and open-loop request schedules.
+//!
+//! A client that only issues a request once the previous one is done hides stalls: the requests
+//! it would have issued during a stall are never sent, so none of them show the delay. Paced
+//! clients instead follow a [`Schedule`] fixed in advance and measure latency from when each
+//! request was meant to start, which includes the time it spent queued behind a stall.
+
+use std::{fmt, hint, str::FromStr, thread, time::{Duration, Instant}};
+
+use rand::rngs::StdRng;
+use rand_distr::{Distribution, Exp};
+
+// Each power of two is split into this many buckets, so recorded values are within about 6%
+const SUB_BUCKET_BITS: u32 = 4;
+const SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+const BUCKETS: usize = (64 - SUB_BUCKET_BITS as usize + 1) * SUB_BUCKETS;
+
+// Waits shorter than this are spun rather than slept, since sleeps overshoot by about as much
+const SPIN_THRESHOLD: Duration = Duration::from_micros(100);
+
+/// Latencies in log-linear buckets of nanoseconds.
+#[derive(Clone)]
+pub struct Histogram {
+    counts: Vec<u64>,
+    count: u64,
+    max: Duration,
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Self {
+            counts: vec![0; BUCKETS],
+            count: 0,
+            max: Duration::ZERO,
+        }
+    }
+
+    pub fn record(&mut self, latency: Duration) {
+        let nanos = u64::try_from(latency.as_nanos()).unwrap_or(u64::MAX);
+
+        self.counts[bucket(nanos)] += 1;
+        self.count += 1;
+        self.max = self.max.max(latency);
+    }
+
+    /// The latency that `quantile` of the recorded latencies are at or below, rounded up to the end
+    /// of its bucket.
+    pub fn quantile(&self, quantile: f64) -> Duration {
+        let rank = ((quantile * self.count as f64).ceil() as u64).max(1);
+
+        let mut seen = 0;
+        for (bucket, &count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Duration::from_nanos(highest_in(bucket)).min(self.max);
+            }
+        }
+
+        self.max
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        for (count, &other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+        self.count += other.count;
+        self.max = self.max.max(other.max);
+    }
+}
+
+/// Percentiles in microseconds.
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let micros = |latency: Duration| latency.as_secs_f64() * 1e6;
+
+        write!(
+            f,
+            "p50 {:<10.1}p90 {:<10.1}p99 {:<10.1}p99.9 {:<10.1}max {:.1}",
+            micros(self.quantile(0.5)),
+            micros(self.quantile(0.9)),
+            micros(self.quantile(0.99)),
+            micros(self.quantile(0.999)),
+            micros(self.max)
+        )
+    }
+}
+
+fn bucket(nanos: u64) -> usize {
+    if nanos < SUB_BUCKETS as u64 {
+        return nanos as usize;
+    }
+
+    // The top SUB_BUCKET_BITS + 1 bits of nanos pick the bucket
+    let shift = 63 - nanos.leading_zeros() - SUB_BUCKET_BITS;
+    let sub_bucket = (nanos >> shift) as usize - SUB_BUCKETS;
+
+    (shift as usize + 1) * SUB_BUCKETS + sub_bucket
+}
+
+fn highest_in(bucket: usize) -> u64 {
+    if bucket < SUB_BUCKETS {
+        return bucket as u64;
+    }
+
+    let shift = bucket / SUB_BUCKETS - 1;
+    let sub_bucket = (bucket % SUB_BUCKETS + SUB_BUCKETS) as u64;
+
+    // The top bucket ends past u64::MAX
+    let end = (u128::from(sub_bucket) + 1) << shift;
+    u64::try_from(end - 1).unwrap_or(u64::MAX)
+}
+
+/// How the requests of a paced client are spread out in time.
+#[derive(Clone, Copy)]
+pub enum Arrivals {
+    /// The same gap between every request
+    Constant,
+    /// Exponentially distributed gaps, like requests from many independent users. Clients with
+    /// Poisson arrivals add up to Poisson arrivals at their total rate.
+    Poisson,
+}
+
+impl FromStr for Arrivals {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, ()> {
+        match name.to_ascii_lowercase().as_str() {
+            "constant" => Ok(Self::Constant),
+            "poisson" => Ok(Self::Poisson),
+            _ => Err(()),
+        }
+    }
+}
+
+/// When a paced client means to start each of its requests, fixed in advance so that a slow
+/// request doesn't push back the ones after it. A client that falls behind issues the requests it
+/// owes straight away.
+pub struct Schedule {
+    start: Instant,
+    // Requests per second
+    rate: f64,
+    arrivals: Arrivals,
+    issued: u64,
+    // Seconds from start to the next Poisson arrival
+    next_arrival: f64,
+    rng: StdRng,
+}
+
+impl Schedule {
+    pub fn new(start: Instant, rate: f64, arrivals: Arrivals, rng: StdRng) -> Self {
+        Self {
+            start,
+            rate,
+            arrivals,
+            issued: 0,
+            next_arrival: 0.0,
+            rng,
+        }
+    }
+
+    /// When the next request is meant to start.
+    pub fn next(&mut self) -> Instant {
+        let offset = match self.arrivals {
+            // Computed from the start rather than summed so rounding doesn't build up
+            Arrivals::Constant => self.issued as f64 / self.rate,
+            Arrivals::Poisson => {
+                let offset = self.next_arrival;
+                self.next_arrival += Exp::new(self.rate).unwrap().sample(&mut self.rng);
+                offset
+            },
+        };
+        self.issued += 1;
+
+        self.start + Duration::from_secs_f64(offset)
+    }
+}
+
+/// Sleeps, then spins, until `deadline`.
+pub fn wait_until(deadline: Instant) {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining > SPIN_THRESHOLD {
+        thread::sleep(remaining - SPIN_THRESHOLD);
+    }
+
+    while Instant::now() < deadline {
+        hint::spin_loop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_values_have_their_own_buckets() {
+        for nanos in 0 .. SUB_BUCKETS as u64 {
+            assert_eq!(bucket(nanos), nanos as usize);
+            assert_eq!(highest_in(bucket(nanos)), nanos);
+        }
+
+        assert_eq!(bucket(16), 16);
+        assert_eq!(bucket(31), 31);
+        assert_eq!(bucket(32), 32);
+        assert_eq!(bucket(33), 32);
+    }
+
+    #[test]
+    fn buckets_cover_every_value() {
+        for bucket_index in 0 .. BUCKETS {
+            let highest = highest_in(bucket_index);
+            assert_eq!(bucket(highest), bucket_index);
+            if bucket_index + 1 < BUCKETS {
+                assert_eq!(bucket(highest + 1), bucket_index + 1);
+            }
+        }
+
+        assert_eq!(bucket(u64::MAX), BUCKETS - 1);
+        assert_eq!(highest_in(BUCKETS - 1), u64::MAX);
+    }
+
+    #[test]
+    fn buckets_are_within_a_sixteenth() {
+        for nanos in [17, 100, 1_000, 12_345, 1_000_000, 999_999_999, 1 << 40, u64::MAX / 3] {
+            let highest = highest_in(bucket(nanos));
+            assert!(highest >= nanos);
+            assert!((highest - nanos) as f64 <= nanos as f64 / SUB_BUCKETS as f64);
+        }
+    }
+
+    #[test]
+    fn quantiles() {
+        let mut histogram = Histogram::new();
+        for micros in 1 ..= 1000 {
+            histogram.record(Duration::from_micros(micros));
+        }
+
+        let close = |quantile: f64, micros: u64| {
+            let expected = Duration::from_micros(micros);
+            let found = histogram.quantile(quantile);
+            found >= expected && found.as_secs_f64() <= expected.as_secs_f64() * 1.0625
+        };
+        assert!(close(0.5, 500));
+        assert!(close(0.9, 900));
+        assert!(close(0.99, 990));
+        assert!(close(0.0, 1));
+        assert_eq!(histogram.quantile(1.0), Duration::from_micros(1000));
+    }
+
+    #[test]
+    fn quantiles_stop_at_max() {
+        let mut histogram = Histogram::new();
+        assert_eq!(histogram.quantile(0.5), Duration::ZERO);
+
+        histogram.record(Duration::from_nanos(1_000_001));
+        assert_eq!(histogram.quantile(0.5), Duration::from_nanos(1_000_001));
+
+        // In the top bucket, which ends past u64::MAX nanoseconds
+        histogram.record(Duration::from_nanos(u64::MAX));
+        assert_eq!(histogram.quantile(1.0), Duration::from_nanos(u64::MAX));
+    }
+
+    #[test]
+    fn merged_histograms_count_both() {
+        let mut fast = Histogram::new();
+        let mut slow = Histogram::new();
+        for _ in 0 .. 90 {
+            fast.record(Duration::from_nanos(10));
+        }
+        for _ in 0 .. 10 {
+            slow.record(Duration::from_nanos(5));
+        }
+        slow.record(Duration::from_millis(1));
+
+        fast.merge(&slow);
+
+        assert_eq!(fast.count, 101);
+        assert_eq!(fast.quantile(0.05), Duration::from_nanos(5));
+        assert_eq!(fast.quantile(0.5), Duration::from_nanos(10));
+        assert_eq!(fast.quantile(1.0), Duration::from_millis(1));
+    }
+}
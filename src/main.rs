@@ -1,70 +1,539 @@
 #![feature(generic_associated_types)]
 
-use std::{collections::{hash_map::RandomState, HashMap, HashSet}, sync::{Arc, atomic::{AtomicBool, Ordering}}, thread, time::{Instant, Duration}};
+use std::{borrow::Cow, collections::{HashMap, HashSet}, env, fmt, hash::BuildHasher, mem, str::FromStr, sync::{Arc, Mutex, atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering}}, thread, time::{Instant, Duration}};
 use adapters::{ArcHashMap, FlashMap, EvMap, DashMap, FlurryMap};
 use rand::prelude::*;
 use api::ConcurrentMap;
 use usync::Barrier;
-use rand::seq::SliceRandom;
+use rand::{distributions::WeightedIndex, seq::SliceRandom};
+use rand_distr::Zipf;
 
-use crate::api::{ReadHandle, ReadGuard};
+use hashers::SipHash;
+use execution::{Aggregate, ExecutionWindow};
+use latency::{Arrivals, Histogram, Schedule};
+use memory::CountingAllocator;
+use trace::{TraceEvent, TraceOp};
+use types::{BenchKey, BenchValue};
+use workload::{KeyDistribution, Operation, Phase, WorkloadSpec, Ycsb};
+
+use crate::api::{ReadHandle, ReadGuard, WriteHandle, WriteGuard, WriteOperation};
 
 mod adapters;
 mod api;
+mod execution;
+mod hashers;
+mod latency;
+mod memory;
+mod trace;
+mod types;
+mod workload;
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
 
 const TOTAL_OPS: usize = 20_000_000;
+// Keys in the map, and the keys readers miss with, are drawn from a space this many times larger
+// than the map
+const KEY_SPACE_PER_ENTRY: usize = TOTAL_OPS / 1_000_000;
+// Readers cycle through at most this many pre-built keys, so large keys don't exhaust memory
+const MAX_KEYS_PER_READER: usize = 1 << 20;
+// Maps of large values hold fewer entries so they still fit in memory
+const MAX_MAP_BYTES: usize = 1 << 30;
+
+// Every random choice is drawn from a stream derived from this seed
+static SEED: AtomicU64 = AtomicU64::new(0);
 
 fn main() {
+    let mut args = env::args().skip(1).collect::<Vec<_>>();
+
+    // `--seed <seed>` repeats the data sets and random choices of an earlier run
+    let seed = match args.first().map(String::as_str) {
+        Some("--seed") => {
+            let seed = args.get(1)
+                .and_then(|seed| seed.parse().ok())
+                .unwrap_or_else(|| panic!("usage: --seed <u64>"));
+            args.drain(.. 2);
+            seed
+        },
+        _ => thread_rng().gen(),
+    };
+    SEED.store(seed, Ordering::Relaxed);
+    println!("Seed: {}", seed);
+
     // let num_physical = num_cpus::get_physical();
     let num_logical = num_cpus::get();
+    // Leaves a CPU to the writer in benchmarks that have one
+    let num_readers = num_logical.saturating_sub(1).max(1);
+
+    // Workload files or presets given on the command line replace the built-in benchmarks, as do
+    // these subcommands:
+    //
+    //   record <workload> <trace>
+    //   import <access log> <trace>
+    //   replay <trace> [timed]
+    //   read-only [--lookups-per-guard <n>] [read workload...]
+    //   read-write [--read-rate <reads per second>] [--arrivals constant|poisson]
+    //              [--lookups-per-guard <n>] [write workload...]
+    //   sweep [--lookups-per-guard <n>] [read workload...]
+    //   ycsb
+    //   construction
+    //   growth
+    //   slow-readers
+    //   handle-churn
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [] => (),
+        ["record", workload, path] => {
+            let spec = load_workload(workload);
+
+            println!("{}", spec.name);
+            println!("Name              Phase               Throughput (op/s)       Latency (ns)    Hit ratio       Reads           Writes          Recovery (ms)");
+            workload::record(&spec, path)
+                .unwrap_or_else(|error| panic!("failed to write {}: {}", path, error));
+            return;
+        },
+        ["import", log, path] => {
+            let events = trace::import_log(log)
+                .unwrap_or_else(|error| panic!("failed to import {}: {}", log, error));
+
+            trace::print_summary(&events);
+            trace::write_trace(path, events)
+                .unwrap_or_else(|error| panic!("failed to write {}: {}", path, error));
+            return;
+        },
+        ["replay", path, ref mode @ ..] => {
+            let timed = match mode {
+                [] => false,
+                ["timed"] => true,
+                _ => panic!("usage: replay <trace> [timed]"),
+            };
+            let events = trace::read_trace(path)
+                .unwrap_or_else(|error| panic!("failed to load {}: {}", path, error));
+
+            trace::print_summary(&events);
+            println!();
+            println!("Name              Throughput (op/s)       Latency (ns)    Hit ratio       Events          Mean lag (us)   Max lag (us)");
+            bench_replay::<FlashMap, u64, u64, SipHash>(&events, timed, "flashmap");
+            bench_replay::<EvMap, u64, u64, SipHash>(&events, timed, "evmap");
+            bench_replay::<DashMap<_, _, _>, u64, u64, SipHash>(&events, timed, "dashmap");
+            bench_replay::<FlurryMap<_, _, _>, u64, u64, SipHash>(&events, timed, "flurry");
+            return;
+        },
+        // Every read workload unless some are named
+        ["read-only", ref options @ ..] => {
+            let mut lookups_per_guard = 1;
+            let mut workloads = Vec::new();
+
+            let mut options = options.iter();
+            while let Some(&option) = options.next() {
+                match option {
+                    "--lookups-per-guard" => lookups_per_guard = parse_lookups_per_guard(options.next().copied()),
+                    name => workloads.push(name.parse()
+                        .unwrap_or_else(|_| panic!("unknown read workload {}", name))),
+                }
+            }
+            if workloads.is_empty() {
+                workloads = ReadWorkload::ALL.to_vec();
+            }
+
+            // Other key types: StringKey<64>, Arc<str>. Other value types: [u8; 64], [u8; 1024],
+            // [u8; 16384]. Other hashers: FxHash, AHash, IdentityHash.
+            for workload in workloads {
+                println!("{}, {} lookups per guard", workload, lookups_per_guard);
+                println!("Name              Entries         Working set     Hit ratio       Throughput (op/s)       Latency (ns)    Allocs/read");
+                bench_one_read_only::<ArcHashMap<_, _, _>, u64, u64, SipHash>(num_logical, 1_000_000, 0.5, lookups_per_guard, workload, "Arc<HashMap>");
+                bench_one_read_only::<FlashMap, u64, u64, SipHash>(num_logical, 1_000_000, 0.5, lookups_per_guard, workload, "flashmap");
+                bench_one_read_only::<EvMap, u64, u64, SipHash>(num_logical, 1_000_000, 0.5, lookups_per_guard, workload, "evmap");
+                bench_one_read_only::<DashMap<_, _, _>, u64, u64, SipHash>(num_logical, 1_000_000, 0.5, lookups_per_guard, workload, "dashmap");
+                bench_one_read_only::<FlurryMap<_, _, _>, u64, u64, SipHash>(num_logical, 1_000_000, 0.5, lookups_per_guard, workload, "flurry");
+            }
+            return;
+        },
+        // Every write workload unless some are named. Readers look keys up as fast as they can
+        // unless given a total rate; sweeping the rate finds the knee, where achieved throughput
+        // stops keeping up and corrected read latency takes off.
+        ["read-write", ref options @ ..] => {
+            let mut reads_per_second = None;
+            let mut arrivals = None;
+            let mut lookups_per_guard = 1;
+            let mut workloads = Vec::new();
+
+            let mut options = options.iter();
+            while let Some(&option) = options.next() {
+                match option {
+                    "--read-rate" => reads_per_second = Some(options.next()
+                        .and_then(|rate| rate.parse().ok())
+                        .filter(|&rate: &usize| rate > 0)
+                        .unwrap_or_else(|| panic!("usage: --read-rate <reads per second, at least 1>"))),
+                    "--arrivals" => arrivals = Some(options.next()
+                        .and_then(|arrivals| arrivals.parse().ok())
+                        .unwrap_or_else(|| panic!("usage: --arrivals constant|poisson"))),
+                    "--lookups-per-guard" => lookups_per_guard = parse_lookups_per_guard(options.next().copied()),
+                    name => workloads.push(name.parse()
+                        .unwrap_or_else(|_| panic!("unknown write workload {}", name))),
+                }
+            }
+            if workloads.is_empty() {
+                workloads = WriteWorkload::ALL.to_vec();
+            }
+
+            let read_load = match (reads_per_second, arrivals) {
+                (None, None) => ReadLoad::Saturating,
+                (None, Some(_)) => panic!("--arrivals needs --read-rate"),
+                (Some(reads_per_second), arrivals) => ReadLoad::Paced(reads_per_second, arrivals.unwrap_or(Arrivals::Constant)),
+            };
+
+            for workload in workloads {
+                let params = MixedParams {
+                    lookups_per_guard,
+                    read_load,
+                    workload,
+                    ..MixedParams::new(num_readers)
+                };
+
+                println!("{}", workload);
+                println!("Name              Throughput (op/s)       Latency (ns)    Hit ratio       Allocs/read     Allocs/write    Resident (B)    Per entry (B)   Peak (B)        After drop (B)");
+                bench_one::<FlashMap, u64, u64, SipHash>(params, "flashmap");
+                bench_one::<EvMap, u64, u64, SipHash>(params, "evmap");
+                bench_one::<DashMap<_, _, _>, u64, u64, SipHash>(params, "dashmap");
+                bench_one::<FlurryMap<_, _, _>, u64, u64, SipHash>(params, "flurry");
+            }
+            return;
+        },
+        // Only get_and_test unless read workloads are named
+        ["sweep", ref options @ ..] => {
+            let mut lookups_per_guard = 1;
+            let mut workloads = Vec::new();
+
+            let mut options = options.iter();
+            while let Some(&option) = options.next() {
+                match option {
+                    "--lookups-per-guard" => lookups_per_guard = parse_lookups_per_guard(options.next().copied()),
+                    name => workloads.push(name.parse()
+                        .unwrap_or_else(|_| panic!("unknown read workload {}", name))),
+                }
+            }
+            if workloads.is_empty() {
+                workloads = vec![ReadWorkload::GetAndTest];
+            }
 
-    // println!("Name              Throughput (op/s)       Latency (ns)");
-    // bench_one_read_only::<ArcHashMap<_, _, _>>(num_logical, "Arc<HashMap>");
-    // bench_one_read_only::<FlashMap>(num_logical, "flashmap");
-    // bench_one_read_only::<EvMap>(num_logical, "evmap");
-    // bench_one_read_only::<DashMap<_, _, _>>(num_logical, "dashmap");
-    // bench_one_read_only::<FlurryMap<_, _, _>>(num_logical, "flurry");
+            for workload in workloads {
+                println!("{}, {} lookups per guard", workload, lookups_per_guard);
+                println!("Name              Entries         Working set     Hit ratio       Throughput (op/s)       Latency (ns)    Allocs/read");
+                sweep_read_only::<ArcHashMap<_, _, _>, u64, u64, SipHash>(num_logical, 0.5, lookups_per_guard, workload, "Arc<HashMap>");
+                sweep_read_only::<FlashMap, u64, u64, SipHash>(num_logical, 0.5, lookups_per_guard, workload, "flashmap");
+                sweep_read_only::<EvMap, u64, u64, SipHash>(num_logical, 0.5, lookups_per_guard, workload, "evmap");
+                sweep_read_only::<DashMap<_, _, _>, u64, u64, SipHash>(num_logical, 0.5, lookups_per_guard, workload, "dashmap");
+                sweep_read_only::<FlurryMap<_, _, _>, u64, u64, SipHash>(num_logical, 0.5, lookups_per_guard, workload, "flurry");
+            }
+            return;
+        },
+        // Every YCSB preset in turn
+        ["ycsb"] => {
+            for preset in Ycsb::ALL {
+                let spec = preset.spec(num_logical);
+
+                println!("{}", spec.name);
+                println!("Name              Phase               Throughput (op/s)       Latency (ns)    Hit ratio       Reads           Writes          Recovery (ms)");
+                workload::run(&spec);
+            }
+            return;
+        },
+        ["construction"] => {
+            println!("Name              Entries         New (ns/entry)      Insert (ns/entry)   Drop write (ns/entry)   Drop read (ns/entry)");
+            bench_construction::<ArcHashMap<_, _, _>, u64, u64, SipHash>("Arc<HashMap>");
+            bench_construction::<FlashMap, u64, u64, SipHash>("flashmap");
+            bench_construction::<EvMap, u64, u64, SipHash>("evmap");
+            bench_construction::<DashMap<_, _, _>, u64, u64, SipHash>("dashmap");
+            bench_construction::<FlurryMap<_, _, _>, u64, u64, SipHash>("flurry");
+            return;
+        },
+        ["growth"] => {
+            println!("Name              Median (ns)     p99.9 (ns)      Max (ns)        Spikes          Reader dip      Largest spikes at size");
+            bench_growth::<FlashMap, u64, u64, SipHash>(num_readers, 1_000_000, "flashmap");
+            bench_growth::<EvMap, u64, u64, SipHash>(num_readers, 1_000_000, "evmap");
+            bench_growth::<DashMap<_, _, _>, u64, u64, SipHash>(num_readers, 1_000_000, "dashmap");
+            bench_growth::<FlurryMap<_, _, _>, u64, u64, SipHash>(num_readers, 1_000_000, "flurry");
+            return;
+        },
+        ["slow-readers"] => {
+            for hold_time in [1, 10, 100].map(Duration::from_millis) {
+                println!("Readers hold guards for {:?}", hold_time);
+                println!("Name              Writes          Acquire (ms)    Acquire max (us)    Publish p99 (us)    Publish max (us)    Growth (B)");
+                bench_slow_readers::<FlashMap, u64, u64, SipHash>(1, 1_000_000, hold_time, "flashmap");
+                bench_slow_readers::<EvMap, u64, u64, SipHash>(1, 1_000_000, hold_time, "evmap");
+                bench_slow_readers::<DashMap<_, _, _>, u64, u64, SipHash>(1, 1_000_000, hold_time, "dashmap");
+                bench_slow_readers::<FlurryMap<_, _, _>, u64, u64, SipHash>(1, 1_000_000, hold_time, "flurry");
+            }
+            return;
+        },
+        ["handle-churn"] => {
+            for lookups_per_handle in [1, 16, 256] {
+                println!("{} lookups per read handle", lookups_per_handle);
+                println!("Name              Cycles (/s)     Clone (ns)      Drop (ns)       Writes          Write p99 (us)  Write max (us)");
+                bench_handle_churn::<FlashMap, u64, u64, SipHash>(num_readers, 1_000_000, lookups_per_handle, "flashmap");
+                bench_handle_churn::<EvMap, u64, u64, SipHash>(num_readers, 1_000_000, lookups_per_handle, "evmap");
+                bench_handle_churn::<DashMap<_, _, _>, u64, u64, SipHash>(num_readers, 1_000_000, lookups_per_handle, "dashmap");
+                bench_handle_churn::<FlurryMap<_, _, _>, u64, u64, SipHash>(num_readers, 1_000_000, lookups_per_handle, "flurry");
+            }
+            return;
+        },
+        _ => {
+            for workload in &args {
+                let spec = load_workload(workload);
+
+                println!("{}", spec.name);
+                println!("Name              Phase               Throughput (op/s)       Latency (ns)    Hit ratio       Reads           Writes          Recovery (ms)");
+                workload::run(&spec);
+            }
+            return;
+        },
+    }
+
+    println!("Name              Throughput (op/s)       Latency (ns)    Hit ratio       Allocs/read     Allocs/write    Resident (B)    Per entry (B)   Peak (B)        After drop (B)");
+    bench_one::<DashMap<_, _, _>, u64, u64, SipHash>(MixedParams::new(num_logical), "dashmap");
+}
+
+// The value of `--lookups-per-guard`. Guard acquisition cost shows up as the difference between
+// one and many lookups per guard.
+fn parse_lookups_per_guard(value: Option<&str>) -> usize {
+    value.and_then(|lookups| lookups.parse().ok())
+        .filter(|&lookups| lookups > 0)
+        .unwrap_or_else(|| panic!("usage: --lookups-per-guard <lookups, at least 1>"))
+}
 
-    bench_one::<DashMap<_, _, _>>(num_logical, 1000, "flashmap");
+// A YCSB preset such as `ycsb-a`, or else the path of a workload file
+fn load_workload(workload: &str) -> WorkloadSpec {
+    match workload.parse::<Ycsb>() {
+        Ok(preset) => preset.spec(num_cpus::get()),
+        Err(_) => WorkloadSpec::from_file(workload)
+            .unwrap_or_else(|error| panic!("failed to load {}: {}", workload, error)),
+    }
+}
+
+// The random stream `stream` of thread `index`, e.g. the keys of one reader. A seed always gives the
+// same numbers, whatever order threads run in.
+fn seeded_rng(stream: &str, index: usize) -> StdRng {
+    let seed = stream.bytes()
+        .chain(index.to_le_bytes())
+        .fold(SEED.load(Ordering::Relaxed), |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3));
+
+    StdRng::seed_from_u64(seed)
+}
+
+fn max_entries<V>(entries: usize) -> usize {
+    entries.min(MAX_MAP_BYTES / mem::size_of::<V>().max(1))
 }
 
-fn bench_one_read_only<M: ConcurrentMap<u64, u64, RandomState>>(num_threads: usize, name: &str) {
-    const NUM_INSERTED: usize = 1_000_000;
+fn key_space(num_inserted: usize) -> u64 {
+    (num_inserted * KEY_SPACE_PER_ENTRY) as u64
+}
+
+// The observed hit ratio, as a percentage
+fn hit_ratio_of(hits: usize, lookups: usize) -> String {
+    format!("{:.1}%", 100.0 * hits as f64 / lookups.max(1) as f64)
+}
 
-    // Put NUM_INSERTED elements in the map
-    let mut rng = thread_rng();
-    let mut map = HashMap::<u64, u64, RandomState>::with_capacity(NUM_INSERTED);
-    let mut num_inserted = 0;
-    while num_inserted < NUM_INSERTED {
-        let val = rng.gen::<u64>() % TOTAL_OPS as u64;
-        if map.insert(val, val * 2).is_none() {
-            num_inserted += 1;
+// Keys for a reader to cycle through, along with their indices. Exactly `hit_ratio` of them are
+// drawn from `present`, the sorted indices in the map, according to `distribution`, and the rest
+// uniformly from the whole key space minus those, so that large maps aren't only probed in a
+// cache-friendly corner.
+fn reader_keys<K: BenchKey>(
+    present: &[u64],
+    key_space: u64,
+    distribution: KeyDistribution,
+    hit_ratio: f64,
+    lookups: usize,
+    rng: &mut StdRng
+) -> (Vec<u64>, Vec<K>) {
+    assert!((0.0 ..= 1.0).contains(&hit_ratio), "hit ratio must be between 0 and 1");
+
+    let lookups = lookups.min(MAX_KEYS_PER_READER);
+    let hits = (lookups as f64 * hit_ratio).round() as usize;
+
+    let mut indices = distribution.sample_present(present, hits, rng);
+    indices.reserve(lookups - hits);
+    while indices.len() < lookups {
+        let index = rng.gen_range(0 .. key_space);
+        if present.binary_search(&index).is_err() {
+            indices.push(index);
         }
     }
+    indices.shuffle(rng);
+
+    let keys = indices.iter().map(|&index| K::from_index(index)).collect();
+
+    (indices, keys)
+}
+
+// Runs the read-only benchmark on maps from a few hundred entries, which fit in L1, to tens of
+// millions, which don't fit in any cache
+fn sweep_read_only<M, K, V, S>(
+    num_threads: usize,
+    hit_ratio: f64,
+    lookups_per_guard: usize,
+    workload: ReadWorkload,
+    name: &str
+)
+where
+    M: ConcurrentMap<K, V, S>,
+    K: BenchKey,
+    V: BenchValue,
+    S: BuildHasher + Default,
+{
+    const SIZES: [usize; 10] = [
+        1 << 8, 1 << 10, 1 << 12, 1 << 14, 1 << 16, 1 << 18, 1 << 20, 1 << 22, 1 << 24, 1 << 25
+    ];
+
+    for num_inserted in SIZES {
+        bench_one_read_only::<M, K, V, S>(num_threads, num_inserted, hit_ratio, lookups_per_guard, workload, name);
+    }
+}
+
+fn bench_one_read_only<M, K, V, S>(
+    num_threads: usize,
+    num_inserted: usize,
+    hit_ratio: f64,
+    lookups_per_guard: usize,
+    workload: ReadWorkload,
+    name: &str
+)
+where
+    M: ConcurrentMap<K, V, S>,
+    K: BenchKey,
+    V: BenchValue,
+    S: BuildHasher + Default,
+{
+    const SCANS_PER_READER: usize = 4;
+
+    assert!(lookups_per_guard > 0);
+
+    let num_inserted = max_entries::<V>(num_inserted);
+    let key_space = key_space(num_inserted);
+
+    // Generate num_inserted key indices
+    let mut rng = seeded_rng("map", 0);
+    let mut indices = HashSet::with_capacity(num_inserted);
+    while indices.len() < num_inserted {
+        indices.insert(rng.gen_range(0 .. key_space));
+    }
+    let mut present = indices.into_iter().collect::<Vec<_>>();
+    present.sort_unstable();
+    let present = Arc::new(present);
+
+    // Put num_inserted elements in the map
+    let baseline = memory::live_bytes();
+    let mut map = HashMap::<K, V, S>::with_capacity_and_hasher(num_inserted, S::default());
+    map.extend(present.iter().map(|&index| (K::from_index(index), V::from_index(index))));
     
     let (write, read) = M::new(map);
+    let working_set = memory::live_bytes().saturating_sub(baseline);
 
     let ops_per_reader = TOTAL_OPS / num_threads;
-    let total_ops = ops_per_reader * num_threads;
 
     let barrier = Arc::new(Barrier::new(num_threads));
     let join_handles = (0..num_threads).map(|i| thread::spawn({
         let read = read.clone();
         let barrier = Arc::clone(&barrier);
+        let present = Arc::clone(&present);
 
         move || {
+            let mut rng = seeded_rng("reader keys", i);
+            let (indices, keys) = reader_keys::<K>(&present, key_space, KeyDistribution::Uniform, hit_ratio, ops_per_reader, &mut rng);
+            let mut lookups = indices.iter().zip(&keys).cycle().take(ops_per_reader).peekable();
+            let mut operations = 0;
+            let mut hits = 0;
+
+            // contains_key has no value to check, so whether each key is in the map is worked out
+            // up front
+            let in_map = match workload {
+                ReadWorkload::ContainsKey => indices.iter().map(|index| present.binary_search(index).is_ok()).collect(),
+                _ => Vec::new(),
+            };
+
             barrier.wait();
 
+            let allocations = memory::thread_allocations();
             let start = Instant::now();
-            for key in (i * ops_per_reader .. (i + 1) * ops_per_reader).map(|k| k as u64) {
-                assert!(read.guard().get_and_test(&key, |&k| k == key * 2).unwrap_or(true));
+            match workload {
+                ReadWorkload::GetAndTest => while lookups.peek().is_some() {
+                    let guard = read.guard();
+                    for (&index, key) in lookups.by_ref().take(lookups_per_guard) {
+                        if let Some(matches) = guard.get_and_test(key, |value| value.index() == index) {
+                            assert!(matches);
+                            hits += 1;
+                        }
+                        operations += 1;
+                    }
+                },
+                ReadWorkload::ContainsKey => while lookups.peek().is_some() {
+                    let guard = read.guard();
+                    for (_, key) in lookups.by_ref().take(lookups_per_guard) {
+                        // Lookups cycle through the keys in order
+                        let expected = in_map[operations % in_map.len()];
+                        if guard.contains_key(key) {
+                            assert!(expected);
+                            hits += 1;
+                        } else {
+                            assert!(!expected);
+                        }
+                        operations += 1;
+                    }
+                },
+                ReadWorkload::GetCloned => while lookups.peek().is_some() {
+                    let guard = read.guard();
+                    for (&index, key) in lookups.by_ref().take(lookups_per_guard) {
+                        if let Some(value) = guard.get_cloned(key) {
+                            assert_eq!(value.index(), index);
+                            hits += 1;
+                        }
+                        operations += 1;
+                    }
+                },
+                // A batch always shares one guard; larger guard lifetimes cover several batches
+                ReadWorkload::GetMany(batch_size) => {
+                    let batches_per_guard = (lookups_per_guard / batch_size).max(1);
+                    let mut batches = indices.chunks(batch_size)
+                        .zip(keys.chunks(batch_size))
+                        .cycle()
+                        .take(ops_per_reader / batch_size)
+                        .peekable();
+
+                    while batches.peek().is_some() {
+                        let guard = read.guard();
+                        for (indices, batch) in batches.by_ref().take(batches_per_guard) {
+                            let mut indices = indices.iter();
+                            guard.get_many(batch, |_, value| {
+                                let index = *indices.next().unwrap();
+                                if let Some(value) = value {
+                                    assert_eq!(value.index(), index);
+                                    hits += 1;
+                                }
+                            });
+                            operations += batch.len();
+                        }
+                    }
+                },
+                // Each scan visits every entry, so count entries rather than scans. Every entry
+                // visited is a hit.
+                ReadWorkload::Fold => for _ in 0 .. SCANS_PER_READER {
+                    let visited = read.guard().fold(0, |visited, key, value| {
+                        assert_eq!(value.index(), key.index());
+                        visited + 1
+                    });
+                    operations += visited;
+                    hits += visited;
+                }
             }
             let end = Instant::now();
+            let allocations = memory::thread_allocations() - allocations;
 
             ExecutionWindow {
                 start,
                 end,
-                operations: ops_per_reader
+                operations,
+                hits,
+                allocations,
+                idle: Duration::ZERO
             }
         }
     }))
@@ -74,26 +543,76 @@ fn bench_one_read_only<M: ConcurrentMap<u64, u64, RandomState>>(num_threads: usi
 
     drop(write);
 
-    let throughput = executions.iter()
-        .map(ExecutionWindow::throughput)
-        .sum::<u64>();
+    let total = Aggregate::of(&executions);
 
-    let total_time = executions.iter()
-        .map(ExecutionWindow::time_elapsed)
-        .sum::<Duration>();
-
-    let avg_latency = total_time.as_nanos() / total_ops as u128;
-
-    println!("{:<18}{:<24}{}", name, throughput, avg_latency);
+    println!(
+        "{:<18}{:<16}{:<16}{:<16}{:<24}{:<16}{}",
+        name,
+        num_inserted,
+        format!("{} KiB", working_set / 1024),
+        hit_ratio_of(total.hits, total.operations),
+        total.throughput(),
+        total.mean_latency().as_nanos(),
+        memory::per_operation(total.allocations, total.operations)
+    );
+    print_readers(&executions, &total);
 }
 
-fn bench_one<M: ConcurrentMap<u64, u64, RandomState>>(
+// What bench_one runs: readers looking up keys while a writer applies a write workload
+#[derive(Clone, Copy)]
+struct MixedParams {
     num_readers: usize,
+    num_inserted: usize,
+    hit_ratio: f64,
+    lookups_per_guard: usize,
+    read_load: ReadLoad,
     writes_per_second: usize,
-    name: &str
-) {
-    const NUM_INSERTED: usize = 1_000_000;
+    batch_size: usize,
+    workload: WriteWorkload,
+}
+
+impl MixedParams {
+    // Saturating readers of a million entries, half of their lookups hits, and a thousand mixed
+    // writes a second, one per guard
+    fn new(num_readers: usize) -> Self {
+        Self {
+            num_readers,
+            num_inserted: 1_000_000,
+            hit_ratio: 0.5,
+            lookups_per_guard: 1,
+            read_load: ReadLoad::Saturating,
+            writes_per_second: 1000,
+            batch_size: 1,
+            workload: WriteWorkload::Mixed,
+        }
+    }
+}
+
+fn bench_one<M, K, V, S>(params: MixedParams, name: &str)
+where
+    M: ConcurrentMap<K, V, S>,
+    K: BenchKey,
+    V: BenchValue,
+    S: BuildHasher + Default,
+{
     const RUN_TIME: usize = 1000; // milliseconds
+    const RETAIN_MODULUS: u64 = 1024;
+
+    let MixedParams {
+        num_readers,
+        num_inserted,
+        hit_ratio,
+        lookups_per_guard,
+        read_load,
+        writes_per_second,
+        batch_size,
+        workload,
+    } = params;
+
+    assert!(lookups_per_guard > 0);
+
+    let num_inserted = max_entries::<V>(num_inserted);
+    let key_space = key_space(num_inserted);
     
     let writes_to_perform = (writes_per_second * RUN_TIME) / 1000;
     let updates = writes_to_perform / 2;
@@ -101,37 +620,76 @@ fn bench_one<M: ConcurrentMap<u64, u64, RandomState>>(
     let inserts = writes_to_perform - updates - removes;
     let mut writes = Vec::with_capacity(writes_to_perform);
 
-    assert!(writes_to_perform < NUM_INSERTED);
+    assert!(writes_to_perform < num_inserted);
 
-    let mut rng = thread_rng();
+    let mut rng = seeded_rng("map", 0);
 
-    // Generate NUM_INSERTED keys
-    let mut keys = HashSet::with_capacity(NUM_INSERTED);
-    while keys.len() < NUM_INSERTED {
-        keys.insert(rng.gen::<u64>() % TOTAL_OPS as u64);
+    // Generate num_inserted key indices, in an order that only depends on the seed
+    let mut indices = HashSet::with_capacity(num_inserted);
+    while indices.len() < num_inserted {
+        indices.insert(rng.gen_range(0 .. key_space));
     }
-    let mut keys = keys.into_iter().collect::<Vec<_>>();
+    let mut indices = indices.into_iter().collect::<Vec<_>>();
+    indices.sort_unstable();
+    indices.shuffle(&mut rng);
 
-    // Put NUM_INSERTED - inserts elements in the map
-    
-    let mut map = HashMap::<u64, u64, RandomState>::with_capacity(NUM_INSERTED);
-    for key in keys.drain(.. NUM_INSERTED - inserts) {
-        if writes.len() < removes {
-            writes.push(WriteOperation::Remove(key));
-        } else if writes.len() < removes + updates {
-            writes.push(WriteOperation::Update(key, key * 2 + 1));
+    // The first num_inserted - inserts indices start out in the map. Writers only ever store
+    // V::from_index or its updated form, which is what readers check for.
+    if let WriteWorkload::Reload = workload {
+        // Throw the whole map away and insert it again, in order
+        writes.push(WriteOperation::Clear);
+        writes.extend(indices.iter().map(|&index| WriteOperation::Insert(K::from_index(index), V::from_index(index))));
+    } else {
+        let absent = indices.split_off(num_inserted - inserts);
+
+        for &index in &indices[.. removes + updates] {
+            let (key, value) = (K::from_index(index), V::from_index(index));
+
+            if writes.len() < removes {
+                writes.push(match workload {
+                    WriteWorkload::ReadModifyWrite => WriteOperation::CompareAndSwap(key, value.clone(), value.updated()),
+                    _ => WriteOperation::Remove(key)
+                });
+            } else {
+                writes.push(match workload {
+                    WriteWorkload::ReadModifyWrite => WriteOperation::Upsert(key, value, V::updated),
+                    _ => WriteOperation::Update(key, value.updated())
+                });
+            }
         }
+        writes.extend(absent.into_iter().map(|index| {
+            let (key, value) = (K::from_index(index), V::from_index(index));
 
-        map.insert(key, key * 2);
+            match workload {
+                WriteWorkload::ReadModifyWrite => WriteOperation::Upsert(key, value, V::updated),
+                _ => WriteOperation::Insert(key, value)
+            }
+        }));
+        assert_eq!(writes.len(), writes_to_perform);
+        writes.shuffle(&mut rng);
+
+        if let WriteWorkload::ReadModifyWrite = workload {
+            writes.insert(writes.len() / 2, WriteOperation::Retain(|_, value| value.index() % RETAIN_MODULUS != 0));
+        }
     }
-    writes.extend(keys.into_iter().map(|key| WriteOperation::Insert(key, key * 2)));
-    assert_eq!(writes.len(), writes_to_perform);
-    writes.shuffle(&mut rng);
     
-    let (write, read) = M::new(map);
+    // Readers aim their hit ratio at the map as it is before any writes
+    let mut present = indices.clone();
+    present.sort_unstable();
+    let present = Arc::new(present);
+
+    // Everything allocated from here until the handles are dropped belongs to the map, apart from
+    // what the readers allocate before the barrier
+    let baseline = memory::live_bytes();
+
+    let mut map = HashMap::<K, V, S>::with_capacity_and_hasher(num_inserted, S::default());
+    map.extend(indices.iter().map(|&index| (K::from_index(index), V::from_index(index))));
+    let num_entries = map.len();
+
+    let (mut write, read) = M::new(map);
+    let resident = memory::live_bytes().saturating_sub(baseline);
 
     let ops_per_reader = TOTAL_OPS / num_readers;
-    let total_ops = ops_per_reader * num_readers;
 
     let barrier = Arc::new(Barrier::new(num_readers + 1));
     let writer_finished = Arc::new(AtomicBool::new(false));
@@ -140,88 +698,1168 @@ fn bench_one<M: ConcurrentMap<u64, u64, RandomState>>(
         let read = read.clone();
         let barrier = Arc::clone(&barrier);
         let writer_finished = Arc::clone(&writer_finished);
+        let present = Arc::clone(&present);
 
         move || {
-            barrier.wait();
-
-            let mut it = (i * ops_per_reader .. (i + 1) * ops_per_reader).map(|k| k as u64).cycle();
+            let mut rng = seeded_rng("reader keys", i);
+            let (indices, keys) = reader_keys::<K>(&present, key_space, KeyDistribution::Uniform, hit_ratio, ops_per_reader, &mut rng);
+            let mut it = indices.iter().zip(&keys).cycle();
             let mut operations = 0;
+            let mut hits = 0;
+            let mut idle = Duration::ZERO;
+            // Each guard's lookups are one request
+            let mut latencies = match read_load {
+                ReadLoad::Saturating => None,
+                ReadLoad::Paced(..) => Some((Histogram::new(), Histogram::new())),
+            };
 
+            barrier.wait();
+
+            let allocations = memory::thread_allocations();
             let start = Instant::now();
+            let mut schedule = match read_load {
+                ReadLoad::Saturating => None,
+                ReadLoad::Paced(reads_per_second, arrivals) => {
+                    let rate = reads_per_second as f64 / (num_readers * lookups_per_guard) as f64;
+                    Some(Schedule::new(start, rate, arrivals, seeded_rng("reader schedule", i)))
+                },
+            };
             while !writer_finished.load(Ordering::Acquire) {
-                let key = it.next().unwrap();
+                let intended = schedule.as_mut().map(|schedule| {
+                    let intended = schedule.next();
+                    let waiting = Instant::now();
+                    latency::wait_until(intended);
+                    idle += waiting.elapsed();
+                    intended
+                });
+
+                let request_start = Instant::now();
                 let guard = read.guard();
-                assert!(read.guard().get_and_test(&key, |&k| k == key * 2).unwrap_or(true));
+                for (&index, key) in it.by_ref().take(lookups_per_guard) {
+                    if let Some(matches) = guard.get_and_test(key, |value| value.index() == index) {
+                        assert!(matches);
+                        hits += 1;
+                    }
+                }
                 drop(guard);
-                operations += 1;
+                operations += lookups_per_guard;
+
+                if let (Some(intended), Some((latency, corrected))) = (intended, &mut latencies) {
+                    let request_end = Instant::now();
+                    latency.record(request_end - request_start);
+                    corrected.record(request_end - intended);
+                }
             }
             let end = Instant::now();
+            let allocations = memory::thread_allocations() - allocations;
 
-            ExecutionWindow {
+            let execution = ExecutionWindow {
                 start,
                 end,
-                operations
+                operations,
+                hits,
+                allocations,
+                idle
+            };
+            (execution, latencies)
+        }
+    }))
+    .collect::<Vec<_>>();
+
+    let num_writes = writes.len();
+    let mut writes = writes.into_iter().peekable();
+    let mut write_latency = Histogram::new();
+    let mut corrected_write_latency = Histogram::new();
+
+    barrier.wait();
+    let unrelated = memory::live_bytes().saturating_sub(resident);
+    memory::reset_peak();
+    let write_allocations = memory::thread_allocations();
+    // Batches are issued at writes_per_second whether or not earlier ones were slow. Corrected
+    // latencies are measured from when a batch was meant to start, so they include any wait
+    // behind a slow publish. A reload writes the whole map, so it is spread over RUN_TIME instead.
+    let writes_per_second = match workload {
+        WriteWorkload::Reload => num_writes * 1000 / RUN_TIME,
+        _ => writes_per_second,
+    };
+    let rate = writes_per_second as f64 / batch_size as f64;
+    let mut schedule = Schedule::new(Instant::now(), rate, Arrivals::Constant, seeded_rng("writer schedule", 0));
+    while writes.peek().is_some() {
+        let intended = schedule.next();
+        latency::wait_until(intended);
+
+        let start = Instant::now();
+        write.guard().apply_batch(writes.by_ref().take(batch_size));
+        let end = Instant::now();
+
+        write_latency.record(end - start);
+        corrected_write_latency.record(end - intended);
+    }
+    let write_allocations = memory::thread_allocations() - write_allocations;
+    writer_finished.store(true, Ordering::Release);
+    let peak = memory::peak_bytes().saturating_sub(unrelated);
+    
+    let (executions, latencies) = join_handles.into_iter()
+        .map(|handle| handle.join().unwrap())
+        .unzip::<_, _, Vec<_>, Vec<_>>();
+
+    drop(write);
+    let retained = memory::live_bytes().saturating_sub(baseline);
+
+    let reads = Aggregate::of(&executions);
+
+    println!(
+        "{:<18}{:<24}{:<16}{:<16}{:<16}{:<16}{:<16}{:<16}{:<16}{}",
+        name,
+        reads.throughput(),
+        reads.mean_latency().as_nanos(),
+        hit_ratio_of(reads.hits, reads.operations),
+        memory::per_operation(reads.allocations, reads.operations),
+        memory::per_operation(write_allocations, num_writes),
+        resident,
+        resident / num_entries,
+        peak,
+        retained
+    );
+    print_readers(&executions, &reads);
+    if let ReadLoad::Paced(..) = read_load {
+        let mut read_latency = Histogram::new();
+        let mut corrected_read_latency = Histogram::new();
+        for (latency, corrected) in latencies.iter().flatten() {
+            read_latency.merge(latency);
+            corrected_read_latency.merge(corrected);
+        }
+
+        println!("{:<18}Read latency (us)       {}", "", read_latency);
+        println!("{:<18}Corrected (us)          {}", "", corrected_read_latency);
+    }
+    println!("{:<18}Write latency (us)      {}", "", write_latency);
+    println!("{:<18}Corrected (us)          {}", "", corrected_write_latency);
+}
+
+// Times `ConcurrentMap::new` from a populated map, `with_capacity` followed by inserting every
+// entry through one write guard, and dropping each handle of the map built by `new`.
+fn bench_construction<M, K, V, S>(name: &str)
+where
+    M: ConcurrentMap<K, V, S>,
+    K: BenchKey,
+    V: BenchValue,
+    S: BuildHasher + Default,
+{
+    const SIZES: [usize; 4] = [1_000, 10_000, 100_000, 1_000_000];
+
+    for size in SIZES.map(max_entries::<V>) {
+        let entries = || (0 .. size as u64).map(|index| (K::from_index(index), V::from_index(index)));
+        let per_entry = |time: Duration| time.as_nanos() as f64 / size as f64;
+
+        let mut map = HashMap::<K, V, S>::with_capacity_and_hasher(size, S::default());
+        map.extend(entries());
+
+        let start = Instant::now();
+        let (write, read) = M::new(map);
+        let new_time = start.elapsed();
+
+        let start = Instant::now();
+        drop(write);
+        let drop_write_time = start.elapsed();
+
+        let start = Instant::now();
+        drop(read);
+        let drop_read_time = start.elapsed();
+
+        let entries = entries().collect::<Vec<_>>();
+
+        let start = Instant::now();
+        let (mut write, read) = M::with_capacity(size, S::default());
+        let mut guard = write.guard();
+        for (key, value) in entries {
+            guard.insert(key, value);
+        }
+        drop(guard);
+        let insert_time = start.elapsed();
+
+        drop((write, read));
+
+        println!(
+            "{:<18}{:<16}{:<20.1}{:<20.1}{:<24.1}{:.1}",
+            name,
+            size,
+            per_entry(new_time),
+            per_entry(insert_time),
+            per_entry(drop_write_time),
+            per_entry(drop_read_time)
+        );
+    }
+}
+
+// Grows a map from `with_capacity(0, ..)` to `target_size` entries, one insert per write guard,
+// while readers look up the keys being inserted. Inserts much slower than the median are taken
+// to be resizes, and reader throughput around them is compared with the rest of the run.
+fn bench_growth<M, K, V, S>(num_readers: usize, target_size: usize, name: &str)
+where
+    M: ConcurrentMap<K, V, S>,
+    K: BenchKey,
+    V: BenchValue,
+    S: BuildHasher + Default,
+{
+    // An insert this many times slower than the median counts as a spike
+    const SPIKE_FACTOR: u32 = 50;
+    const BUCKET: Duration = Duration::from_millis(1);
+    // Readers only look at the clock every so many lookups
+    const LOOKUPS_PER_SAMPLE: usize = 64;
+    const REPORTED_SPIKES: usize = 8;
+
+    let target_size = max_entries::<V>(target_size);
+    let entries = (0 .. target_size as u64)
+        .map(|index| (K::from_index(index), V::from_index(index)))
+        .collect::<Vec<_>>();
+
+    let (mut write, read) = M::with_capacity(0, S::default());
+
+    let barrier = Arc::new(Barrier::new(num_readers + 1));
+    let writer_finished = Arc::new(AtomicBool::new(false));
+
+    let join_handles = (0..num_readers).map(|i| thread::spawn({
+        let read = read.clone();
+        let barrier = Arc::clone(&barrier);
+        let writer_finished = Arc::clone(&writer_finished);
+
+        move || {
+            // Each reader starts at a different point of the key space
+            let offset = (i * target_size / num_readers) as u64;
+            let (indices, keys): (Vec<_>, Vec<_>) = (0 .. target_size.min(MAX_KEYS_PER_READER) as u64)
+                .map(|index| (index + offset) % target_size as u64)
+                .map(|index| (index, K::from_index(index)))
+                .unzip();
+            let mut it = indices.iter().zip(&keys).cycle();
+            let mut lookups_per_bucket = Vec::<usize>::new();
+
+            barrier.wait();
+
+            let start = Instant::now();
+            while !writer_finished.load(Ordering::Acquire) {
+                for (&index, key) in it.by_ref().take(LOOKUPS_PER_SAMPLE) {
+                    assert!(read.guard().get_and_test(key, |value| value.index() == index).unwrap_or(true));
+                }
+
+                let bucket = (start.elapsed().as_nanos() / BUCKET.as_nanos()) as usize;
+                if lookups_per_bucket.len() <= bucket {
+                    lookups_per_bucket.resize(bucket + 1, 0);
+                }
+                lookups_per_bucket[bucket] += LOOKUPS_PER_SAMPLE;
             }
+
+            lookups_per_bucket
         }
     }))
     .collect::<Vec<_>>();
 
+    // When each insert started relative to the start of the run, and how long it took
+    let mut inserts = Vec::<(Duration, Duration)>::with_capacity(target_size);
+
     barrier.wait();
-    for write in writes {
-        match write {
-            WriteOperation::Insert(key, value) => {
-                let start = Instant::now();
-            },
-            WriteOperation::Remove(key) => {
+    let start = Instant::now();
+    for (key, value) in entries {
+        let insert_start = Instant::now();
+        write.guard().insert(key, value);
+        let insert_end = Instant::now();
 
-            },
-            WriteOperation::Update(key, value) => {
+        inserts.push((insert_start - start, insert_end - insert_start));
+    }
+    writer_finished.store(true, Ordering::Release);
+
+    let mut lookups_per_bucket = Vec::<usize>::new();
+    for handle in join_handles {
+        let reader_lookups = handle.join().unwrap();
+        if lookups_per_bucket.len() < reader_lookups.len() {
+            lookups_per_bucket.resize(reader_lookups.len(), 0);
+        }
+        for (total, lookups) in lookups_per_bucket.iter_mut().zip(reader_lookups) {
+            *total += lookups;
+        }
+    }
+
+    drop(write);
+
+    let mut latencies = inserts.iter().map(|&(_, latency)| latency).collect::<Vec<_>>();
+    latencies.sort_unstable();
+    let median = latencies[latencies.len() / 2];
+    let p999 = latencies[latencies.len() * 999 / 1000];
+    let max = latencies[latencies.len() - 1];
+
+    // The map holds `size` entries once the spiking insert is done
+    let mut spikes = inserts.iter()
+        .enumerate()
+        .filter(|(_, &(_, latency))| latency > median.max(Duration::from_nanos(1)) * SPIKE_FACTOR)
+        .map(|(i, &(offset, latency))| (i + 1, offset, latency))
+        .collect::<Vec<_>>();
+
+    // Compare lookups in the buckets where spikes happened with the average bucket, ignoring
+    // the partially filled bucket readers were in when the writer finished
+    lookups_per_bucket.pop();
+    let mut spike_buckets = spikes.iter()
+        .map(|&(_, offset, _)| (offset.as_nanos() / BUCKET.as_nanos()) as usize)
+        .filter(|&bucket| bucket < lookups_per_bucket.len())
+        .collect::<Vec<_>>();
+    spike_buckets.dedup();
+
+    let reader_dip = if lookups_per_bucket.is_empty() || spike_buckets.is_empty() {
+        0.0
+    } else {
+        let typical = lookups_per_bucket.iter().sum::<usize>() as f64 / lookups_per_bucket.len() as f64;
+        let during_spikes = spike_buckets.iter()
+            .map(|&bucket| lookups_per_bucket[bucket] as f64)
+            .sum::<f64>() / spike_buckets.len() as f64;
+
+        100.0 * (1.0 - during_spikes / typical.max(1.0))
+    };
+
+    spikes.sort_unstable_by_key(|&(_, _, latency)| std::cmp::Reverse(latency));
+    let mut largest = spikes.iter()
+        .take(REPORTED_SPIKES)
+        .map(|&(size, _, _)| size)
+        .collect::<Vec<_>>();
+    largest.sort_unstable();
 
+    println!(
+        "{:<18}{:<16}{:<16}{:<16}{:<16}{:<16}{:?}",
+        name,
+        median.as_nanos(),
+        p999.as_nanos(),
+        max.as_nanos(),
+        spikes.len(),
+        format!("{:.1}%", reader_dip),
+        largest
+    );
+}
+
+// Updates the map one write guard at a time while `num_slow_readers` readers each keep a read
+// guard open for `hold_time` before taking the next one. Acquiring a write guard and publishing
+// it are timed separately, since left-right maps can block in either, and the memory the map
+// grows by shows garbage held back by the open guards.
+fn bench_slow_readers<M, K, V, S>(
+    num_slow_readers: usize,
+    num_inserted: usize,
+    hold_time: Duration,
+    name: &str
+)
+where
+    M: ConcurrentMap<K, V, S>,
+    K: BenchKey,
+    V: BenchValue,
+    S: BuildHasher + Default,
+{
+    const RUN_TIME: Duration = Duration::from_secs(1);
+    // Timings are recorded into a buffer allocated up front, so it doesn't count as growth
+    const MAX_WRITES: usize = 1 << 22;
+
+    let num_inserted = max_entries::<V>(num_inserted);
+
+    let mut map = HashMap::<K, V, S>::with_capacity_and_hasher(num_inserted, S::default());
+    map.extend((0 .. num_inserted as u64).map(|index| (K::from_index(index), V::from_index(index))));
+
+    let (mut write, read) = M::new(map);
+
+    let barrier = Arc::new(Barrier::new(num_slow_readers + 1));
+    let writer_finished = Arc::new(AtomicBool::new(false));
+
+    let join_handles = (0..num_slow_readers).map(|i| thread::spawn({
+        let read = read.clone();
+        let barrier = Arc::clone(&barrier);
+        let writer_finished = Arc::clone(&writer_finished);
+
+        move || {
+            let mut rng = seeded_rng("reader keys", i);
+
+            barrier.wait();
+
+            while !writer_finished.load(Ordering::Acquire) {
+                let index = rng.gen_range(0 .. num_inserted as u64);
+                let guard = read.guard();
+                assert!(guard.get_and_test(&K::from_index(index), |value| value.index() == index).unwrap());
+                thread::sleep(hold_time);
+                drop(guard);
             }
         }
+    }))
+    .collect::<Vec<_>>();
+
+    let mut rng = seeded_rng("writer keys", 0);
+    // How long each write waited for its guard, and how long it took to apply and publish
+    let mut writes = Vec::<(Duration, Duration)>::with_capacity(MAX_WRITES);
+
+    barrier.wait();
+    let resident = memory::live_bytes();
+    memory::reset_peak();
+    let start = Instant::now();
+    while start.elapsed() < RUN_TIME && writes.len() < MAX_WRITES {
+        let index = rng.gen_range(0 .. num_inserted as u64);
+        let value = V::from_index(index);
+        let value = if writes.len().is_multiple_of(2) { value.updated() } else { value };
+
+        let acquire_start = Instant::now();
+        let mut guard = write.guard();
+        let publish_start = Instant::now();
+        guard.update(K::from_index(index), value);
+        drop(guard);
+        let publish_end = Instant::now();
+
+        writes.push((publish_start - acquire_start, publish_end - publish_start));
     }
     writer_finished.store(true, Ordering::Release);
-    
-    let executions = join_handles.into_iter().map(|handle| handle.join().unwrap()).collect::<Vec<_>>();
+    let growth = memory::peak_bytes().saturating_sub(resident);
+
+    for handle in join_handles {
+        handle.join().unwrap();
+    }
+
+    drop(write);
+
+    let acquire_total = writes.iter().map(|&(acquire, _)| acquire).sum::<Duration>();
+    let acquire_max = writes.iter().map(|&(acquire, _)| acquire).max().unwrap_or_default();
+
+    let mut publishes = writes.iter().map(|&(_, publish)| publish).collect::<Vec<_>>();
+    publishes.sort_unstable();
+    let publish_p99 = publishes.get(publishes.len() * 99 / 100).copied().unwrap_or_default();
+    let publish_max = publishes.last().copied().unwrap_or_default();
+
+    println!(
+        "{:<18}{:<16}{:<16}{:<20}{:<20}{:<20}{}",
+        name,
+        writes.len(),
+        acquire_total.as_millis(),
+        acquire_max.as_micros(),
+        publish_p99.as_micros(),
+        publish_max.as_micros(),
+        growth
+    );
+}
+
+// Readers repeatedly clone a read handle, do `lookups_per_handle` lookups through it and drop it,
+// as short-lived reader threads would, while the writer updates the map one guard at a time.
+// Cloning and dropping are timed on the readers and every write is timed on the writer, since
+// left-right maps have to register and unregister each handle with the writer.
+fn bench_handle_churn<M, K, V, S>(
+    num_readers: usize,
+    num_inserted: usize,
+    lookups_per_handle: usize,
+    name: &str
+)
+where
+    M: ConcurrentMap<K, V, S>,
+    K: BenchKey,
+    V: BenchValue,
+    S: BuildHasher + Default,
+{
+    const RUN_TIME: Duration = Duration::from_secs(1);
+    const MAX_WRITES: usize = 1 << 22;
+
+    let num_inserted = max_entries::<V>(num_inserted);
+
+    let mut map = HashMap::<K, V, S>::with_capacity_and_hasher(num_inserted, S::default());
+    map.extend((0 .. num_inserted as u64).map(|index| (K::from_index(index), V::from_index(index))));
+
+    let (mut write, read) = M::new(map);
+
+    let barrier = Arc::new(Barrier::new(num_readers + 1));
+    let writer_finished = Arc::new(AtomicBool::new(false));
+
+    let join_handles = (0..num_readers).map(|i| thread::spawn({
+        let read = read.clone();
+        let barrier = Arc::clone(&barrier);
+        let writer_finished = Arc::clone(&writer_finished);
+
+        move || {
+            let mut rng = seeded_rng("reader keys", i);
+            let mut cycles = 0u32;
+            let mut clone_time = Duration::ZERO;
+            let mut drop_time = Duration::ZERO;
+
+            barrier.wait();
+
+            let start = Instant::now();
+            while !writer_finished.load(Ordering::Acquire) {
+                let clone_start = Instant::now();
+                let handle = read.clone();
+                clone_time += clone_start.elapsed();
+
+                for _ in 0 .. lookups_per_handle {
+                    let index = rng.gen_range(0 .. num_inserted as u64);
+                    assert!(handle.guard().get_and_test(&K::from_index(index), |value| value.index() == index).unwrap());
+                }
+
+                let drop_start = Instant::now();
+                drop(handle);
+                drop_time += drop_start.elapsed();
+
+                cycles += 1;
+            }
+            let elapsed = start.elapsed();
+
+            (cycles, elapsed, clone_time, drop_time)
+        }
+    }))
+    .collect::<Vec<_>>();
+
+    let mut rng = seeded_rng("writer keys", 0);
+    let mut writes = Vec::<Duration>::with_capacity(MAX_WRITES);
+
+    barrier.wait();
+    let start = Instant::now();
+    while start.elapsed() < RUN_TIME && writes.len() < MAX_WRITES {
+        let index = rng.gen_range(0 .. num_inserted as u64);
+        let value = V::from_index(index);
+        let value = if writes.len().is_multiple_of(2) { value.updated() } else { value };
+
+        let write_start = Instant::now();
+        write.guard().update(K::from_index(index), value);
+        writes.push(write_start.elapsed());
+    }
+    writer_finished.store(true, Ordering::Release);
+
+    let readers = join_handles.into_iter().map(|handle| handle.join().unwrap()).collect::<Vec<_>>();
 
     drop(write);
 
-    let throughput = executions.iter()
-        .map(ExecutionWindow::throughput)
-        .sum::<u64>();
+    let cycles = readers.iter().map(|&(cycles, ..)| cycles).sum::<u32>().max(1);
+    let cycles_per_second = readers.iter()
+        .map(|&(cycles, elapsed, ..)| cycles as f64 / elapsed.as_secs_f64())
+        .sum::<f64>();
+    let clone_time = readers.iter().map(|&(_, _, clone_time, _)| clone_time).sum::<Duration>();
+    let drop_time = readers.iter().map(|&(.., drop_time)| drop_time).sum::<Duration>();
+
+    writes.sort_unstable();
+    let write_p99 = writes.get(writes.len() * 99 / 100).copied().unwrap_or_default();
+    let write_max = writes.last().copied().unwrap_or_default();
+
+    println!(
+        "{:<18}{:<16.0}{:<16}{:<16}{:<16}{:<16}{}",
+        name,
+        cycles_per_second,
+        (clone_time / cycles).as_nanos(),
+        (drop_time / cycles).as_nanos(),
+        writes.len(),
+        write_p99.as_micros(),
+        write_max.as_micros()
+    );
+}
+
+// Runs a workload loaded from a file or a preset, one phase after the other. Readers and writers
+// run for every phase; writers take turns with the write handle. Each phase reports how long
+// reads took to get back to that phase's typical rate after it started.
+fn bench_workload<M, K, V, S>(spec: &WorkloadSpec, name: &str)
+where
+    M: ConcurrentMap<K, V, S>,
+    K: BenchKey,
+    V: BenchValue,
+    S: BuildHasher + Default,
+{
+    // A phase has recovered once reads reach this fraction of its median rate
+    const RECOVERED: f64 = 0.9;
+
+    let num_inserted = max_entries::<V>(spec.initial_size);
+    let key_space = key_space(num_inserted);
+
+    let mut rng = seeded_rng("map", 0);
+    let mut indices = HashSet::with_capacity(num_inserted);
+    while indices.len() < num_inserted {
+        indices.insert(rng.gen_range(0 .. key_space));
+    }
+    let mut present = indices.into_iter().collect::<Vec<_>>();
+    present.sort_unstable();
+
+    let mut map = HashMap::<K, V, S>::with_capacity_and_hasher(num_inserted, S::default());
+    map.extend(present.iter().map(|&index| (K::from_index(index), V::from_index(index))));
+
+    let (write, read) = M::new(map);
+    let write = Arc::new(Mutex::new(write));
+
+    let num_threads = spec.readers + spec.writers;
+    let state = Arc::new(WorkloadState {
+        spec: spec.clone(),
+        phases: spec.phases(),
+        present,
+        key_space,
+        inserted: AtomicU64::new(0),
+        barrier: Barrier::new(num_threads + 1),
+        phase: AtomicUsize::new(0),
+    });
+
+    let join_handles = (0..num_threads).map(|i| thread::spawn({
+        let read = read.clone();
+        // Readers don't get the write handle
+        let write = (i >= spec.readers).then(|| Arc::clone(&write));
+        let state = Arc::clone(&state);
+
+        move || workload_client::<M, K, V, S>(i, read, write, &state)
+    }))
+    .collect::<Vec<_>>();
+
+    state.barrier.wait();
+    for (i, phase) in state.phases.iter().enumerate() {
+        thread::sleep(phase.duration);
+        state.phase.store(i + 1, Ordering::Release);
+    }
+
+    let clients = join_handles.into_iter().map(|handle| handle.join().unwrap()).collect::<Vec<_>>();
+
+    drop(write);
+
+    let mut reads_per_bucket = Vec::<usize>::new();
+    for (_, client_reads) in &clients {
+        if reads_per_bucket.len() < client_reads.len() {
+            reads_per_bucket.resize(client_reads.len(), 0);
+        }
+        for (total, reads) in reads_per_bucket.iter_mut().zip(client_reads) {
+            *total += reads;
+        }
+    }
+
+    let mut phase_start = Duration::ZERO;
+    for (i, phase) in state.phases.iter().enumerate() {
+        // Clients that didn't get to run during a phase have nothing to report for it
+        let executions = clients.iter()
+            .map(|(phases, _)| phases[i])
+            .filter(|(execution, _)| execution.operations > 0)
+            .collect::<Vec<_>>();
+
+        let total = Aggregate::of(executions.iter().map(|(execution, _)| execution));
+        let writes = executions.iter()
+            .map(|&(_, writes)| writes)
+            .sum::<usize>();
+
+        let first_bucket = (phase_start.as_nanos() / WORKLOAD_BUCKET.as_nanos()) as usize;
+        phase_start += phase.duration;
+        let last_bucket = (phase_start.as_nanos() / WORKLOAD_BUCKET.as_nanos()) as usize;
+        let buckets = reads_per_bucket.get(first_bucket .. last_bucket.min(reads_per_bucket.len())).unwrap_or(&[]);
+
+        let recovery = if buckets.is_empty() {
+            String::from("-")
+        } else {
+            let mut sorted = buckets.to_vec();
+            sorted.sort_unstable();
+            let typical = sorted[sorted.len() / 2] as f64;
+
+            let recovered_after = buckets.iter()
+                .position(|&reads| reads as f64 >= RECOVERED * typical)
+                .unwrap_or(buckets.len());
+            (WORKLOAD_BUCKET * recovered_after as u32).as_millis().to_string()
+        };
+
+        println!(
+            "{:<18}{:<20}{:<24}{:<16}{:<16}{:<16}{:<16}{}",
+            name,
+            phase.name,
+            total.throughput(),
+            total.mean_latency().as_nanos(),
+            hit_ratio_of(total.hits, total.operations - writes),
+            total.operations - writes,
+            writes,
+            recovery
+        );
+    }
+}
+
+// Reads are counted in buckets of this length to see how they change around phase transitions
+const WORKLOAD_BUCKET: Duration = Duration::from_millis(10);
+
+// What the threads of `bench_workload` share
+struct WorkloadState {
+    spec: WorkloadSpec,
+    phases: Vec<Phase>,
+    // The sorted indices of the initial map
+    present: Vec<u64>,
+    key_space: u64,
+    // Inserts take fresh indices from `key_space` upwards, so the most recent inserts are known
+    inserted: AtomicU64,
+    barrier: Barrier,
+    // The phase being run, which is `phases.len()` once the run is over
+    phase: AtomicUsize,
+}
+
+impl WorkloadState {
+    // The index of the `rank`th most recently inserted key, counting the initial map as inserted
+    // in order before the run
+    fn latest(&self, rank: u64) -> u64 {
+        let inserted = self.inserted.load(Ordering::Relaxed);
+
+        if rank < inserted {
+            self.key_space + inserted - 1 - rank
+        } else {
+            let rank = ((rank - inserted) as usize).min(self.present.len() - 1);
+            self.present[self.present.len() - 1 - rank]
+        }
+    }
+}
+
+// What a client of `bench_workload` prepares for each phase before the run starts, so that
+// switching phases doesn't stall it
+struct ClientPhase<K> {
+    sequence: Vec<Operation>,
+    read_indices: Vec<u64>,
+    read_keys: Vec<K>,
+    // Reads of the latest keys depend on what has been inserted so far, so they're picked as
+    // they happen
+    latest: Option<Zipf<f64>>,
+    // Indices of the initial map for writes other than inserts
+    write_indices: Vec<u64>,
+    // This client's share of the phase's write rate
+    writes_per_second: Option<f64>,
+}
+
+impl<K: BenchKey> ClientPhase<K> {
+    fn new(phase: &Phase, state: &WorkloadState, writer: bool, rng: &mut StdRng) -> Self {
+        // Operations are picked ahead of time and cycled through, like keys
+        const OPERATION_SEQUENCE: usize = 1 << 12;
+        const WRITE_KEYS: usize = 1 << 16;
+
+        let operations = match writer {
+            true => phase.operations.all().to_vec(),
+            false => phase.operations.reads(),
+        };
+
+        let choices = WeightedIndex::new(operations.iter().map(|&(_, weight)| weight)).unwrap();
+        let sequence = (0 .. OPERATION_SEQUENCE)
+            .map(|_| operations[choices.sample(rng)].0)
+            .collect();
+
+        let latest = match phase.key_distribution {
+            KeyDistribution::Latest { exponent } => Some(Zipf::new(state.present.len() as u64, exponent).unwrap()),
+            _ => None,
+        };
+        // Keys for every phase are kept around for the whole run, so they share the memory of
+        // one reader
+        let lookups = MAX_KEYS_PER_READER / state.phases.len();
+        let (read_indices, read_keys) = match latest {
+            Some(_) => (Vec::new(), Vec::new()),
+            None => reader_keys::<K>(&state.present, state.key_space, phase.key_distribution, state.spec.hit_ratio, lookups, rng),
+        };
+
+        let write_indices = match writer {
+            true => reader_keys::<K>(&state.present, state.key_space, phase.key_distribution, 1.0, WRITE_KEYS, rng).0,
+            false => Vec::new(),
+        };
+
+        Self {
+            sequence,
+            read_indices,
+            read_keys,
+            latest,
+            write_indices,
+            writes_per_second: phase.writes_per_second.map(|rate| rate as f64 / state.spec.writers as f64),
+        }
+    }
+
+    fn read_key(&self, position: usize, state: &WorkloadState, rng: &mut StdRng) -> (u64, Cow<'_, K>) {
+        match &self.latest {
+            Some(zipf) => {
+                let index = state.latest(zipf.sample(rng) as u64 - 1);
+                (index, Cow::Owned(K::from_index(index)))
+            },
+            None => {
+                let position = position % self.read_indices.len();
+                (self.read_indices[position], Cow::Borrowed(&self.read_keys[position]))
+            },
+        }
+    }
+}
+
+// One reader or writer of `bench_workload`, returning its execution window in each phase with how
+// many of its operations were writes, and how many reads it did in each bucket of the run
+fn workload_client<M, K, V, S>(
+    client: usize,
+    read: M::ReadHandle,
+    write: Option<Arc<Mutex<M::WriteHandle>>>,
+    state: &WorkloadState
+) -> (Vec<(ExecutionWindow, usize)>, Vec<usize>)
+where
+    M: ConcurrentMap<K, V, S>,
+    K: BenchKey,
+    V: BenchValue,
+{
+    // Reads are only counted into buckets every so many operations
+    const OPERATIONS_PER_SAMPLE: usize = 64;
+
+    // Recorded operations belong to this client, whichever handle they go through
+    trace::set_thread(client as u32);
+
+    let spec = &state.spec;
+    let mut rng = seeded_rng("workload client", client);
+    let phases = state.phases.iter()
+        .map(|phase| ClientPhase::<K>::new(phase, state, write.is_some(), &mut rng))
+        .collect::<Vec<_>>();
+
+    let mut scan = Vec::with_capacity(spec.scan_length);
+    let mut pending = Vec::with_capacity(spec.batch_size);
+    let mut windows = Vec::with_capacity(phases.len());
+    let mut reads_per_bucket = Vec::<usize>::new();
+    let (mut read_position, mut write_position) = (0, 0);
+
+    state.barrier.wait();
+
+    let run_start = Instant::now();
+    for (i, phase) in phases.iter().enumerate() {
+        let mut operations = 0;
+        let mut hits = 0;
+        let mut writes = 0;
+        let mut unsampled_reads = 0;
+
+        let allocations = memory::thread_allocations();
+        let start = Instant::now();
+        for &operation in phase.sequence.iter().cycle() {
+            if state.phase.load(Ordering::Acquire) != i {
+                break;
+            }
+
+            // Writes that aren't due yet are skipped
+            if let (false, Some(rate)) = (operation.is_read(), phase.writes_per_second) {
+                if (writes + pending.len()) as f64 >= start.elapsed().as_secs_f64() * rate {
+                    continue;
+                }
+            }
+
+            match operation {
+                Operation::Get => {
+                    let (index, key) = phase.read_key(read_position, state, &mut rng);
+                    if let Some(matches) = read.guard().get_and_test(&key, |value| value.index() == index) {
+                        assert!(matches);
+                        hits += 1;
+                    }
+                },
+                Operation::ContainsKey => {
+                    let (_, key) = phase.read_key(read_position, state, &mut rng);
+                    if read.guard().contains_key(&key) {
+                        hits += 1;
+                    }
+                },
+                Operation::GetCloned => {
+                    let (index, key) = phase.read_key(read_position, state, &mut rng);
+                    if let Some(value) = read.guard().get_cloned(&key) {
+                        assert_eq!(value.index(), index);
+                        hits += 1;
+                    }
+                },
+                // Hash maps have no order, so a scan looks up the keys following its first key in
+                // index order, through one guard: the initial map's keys, then the keys inserted
+                // so far, which all come after them
+                Operation::Scan => {
+                    let (index, _) = phase.read_key(read_position, state, &mut rng);
+                    let first = state.present.partition_point(|&present| present < index);
+                    let inserted_end = state.key_space + state.inserted.load(Ordering::Relaxed);
+                    let length = rng.gen_range(1 ..= spec.scan_length);
+
+                    scan.clear();
+                    scan.extend(state.present[first ..].iter().copied()
+                        .chain(index.max(state.key_space) .. inserted_end)
+                        .take(length)
+                        .map(K::from_index));
+
+                    let mut found = false;
+                    read.guard().get_many(&scan, |key, value| if let Some(value) = value {
+                        assert_eq!(value.index(), key.index());
+                        found = true;
+                    });
+                    if found {
+                        hits += 1;
+                    }
+                },
+                Operation::Insert => {
+                    let index = state.key_space + state.inserted.fetch_add(1, Ordering::Relaxed);
+                    pending.push(WriteOperation::Insert(K::from_index(index), V::from_index(index)));
+                },
+                Operation::Update => {
+                    let index = phase.write_indices[write_position % phase.write_indices.len()];
+                    pending.push(WriteOperation::Update(K::from_index(index), V::from_index(index).updated()));
+                },
+                Operation::Remove => {
+                    let index = phase.write_indices[write_position % phase.write_indices.len()];
+                    pending.push(WriteOperation::Remove(K::from_index(index)));
+                },
+                Operation::Upsert => {
+                    let index = phase.write_indices[write_position % phase.write_indices.len()];
+                    pending.push(WriteOperation::Upsert(K::from_index(index), V::from_index(index), V::updated));
+                },
+                Operation::CompareAndSwap => {
+                    let index = phase.write_indices[write_position % phase.write_indices.len()];
+                    let value = V::from_index(index);
+                    pending.push(WriteOperation::CompareAndSwap(K::from_index(index), value.clone(), value.updated()));
+                },
+            }
+            operations += 1;
+
+            match operation {
+                Operation::Insert => (),
+                _ if operation.is_read() => {
+                    read_position += 1;
+                    unsampled_reads += 1;
+                },
+                _ => write_position += 1,
+            }
+
+            if pending.len() == spec.batch_size {
+                writes += pending.len();
+                write.as_ref().unwrap().lock().unwrap().guard().apply_batch(pending.drain(..));
+            }
+
+            if operations % OPERATIONS_PER_SAMPLE == 0 {
+                let bucket = (run_start.elapsed().as_nanos() / WORKLOAD_BUCKET.as_nanos()) as usize;
+                if reads_per_bucket.len() <= bucket {
+                    reads_per_bucket.resize(bucket + 1, 0);
+                }
+                reads_per_bucket[bucket] += mem::take(&mut unsampled_reads);
+            }
+        }
+        if !pending.is_empty() {
+            writes += pending.len();
+            write.as_ref().unwrap().lock().unwrap().guard().apply_batch(pending.drain(..));
+        }
+        let end = Instant::now();
+        let allocations = memory::thread_allocations() - allocations;
+
+        let execution = ExecutionWindow {
+            start,
+            end,
+            operations,
+            hits,
+            allocations,
+            idle: Duration::ZERO
+        };
+        windows.push((execution, writes));
+    }
+
+    (windows, reads_per_bucket)
+}
+
+// Replays a recorded trace on up to one worker per CPU, each taking its share of the trace's
+// threads and interleaving their events in time order. Keys the trace uses before inserting them
+// are in the map from the start, and workers that write take turns with the write handle. When
+// `timed`, each event waits until its original time, and the lag behind that time is reported.
+fn bench_replay<M, K, V, S>(events: &[TraceEvent], timed: bool, name: &str)
+where
+    M: ConcurrentMap<K, V, S>,
+    K: BenchKey,
+    V: BenchValue,
+    S: BuildHasher + Default,
+{
+    let mut first_ops = HashMap::<u64, TraceOp>::new();
+    let mut trace_threads = HashMap::<u32, usize>::new();
+    for event in events {
+        first_ops.entry(event.key).or_insert(event.op);
+        let next_thread = trace_threads.len();
+        trace_threads.entry(event.thread).or_insert(next_thread);
+    }
+
+    let num_workers = trace_threads.len().min(num_cpus::get());
+    let mut workers = vec![Vec::<(Duration, TraceOp, K)>::new(); num_workers];
+    for event in events {
+        workers[trace_threads[&event.thread] % num_workers].push((event.time, event.op, K::from_index(event.key)));
+    }
+    for worker_events in &mut workers {
+        worker_events.sort_by_key(|&(time, ..)| time);
+    }
+
+    let mut map = HashMap::<K, V, S>::with_capacity_and_hasher(first_ops.len(), S::default());
+    map.extend(first_ops.iter()
+        .filter(|&(_, &op)| op != TraceOp::Insert)
+        .map(|(&index, _)| (K::from_index(index), V::from_index(index))));
+
+    let (write, read) = M::new(map);
+    let write = Arc::new(Mutex::new(write));
+
+    let barrier = Arc::new(Barrier::new(num_workers));
+
+    let join_handles = workers.into_iter().map(|worker_events| thread::spawn({
+        let read = read.clone();
+        let write = worker_events.iter()
+            .any(|&(_, op, _)| op != TraceOp::Read)
+            .then(|| Arc::clone(&write));
+        let barrier = Arc::clone(&barrier);
+
+        move || {
+            let mut reads = 0;
+            let mut hits = 0;
+            let mut total_lag = Duration::ZERO;
+            let mut max_lag = Duration::ZERO;
+            let mut idle = Duration::ZERO;
+
+            barrier.wait();
+
+            let allocations = memory::thread_allocations();
+            let start = Instant::now();
+            for (time, op, key) in worker_events.iter().cloned() {
+                if timed {
+                    let waiting = Instant::now();
+                    latency::wait_until(start + time);
+                    idle += waiting.elapsed();
+                    let lag = start.elapsed().saturating_sub(time);
+                    total_lag += lag;
+                    max_lag = max_lag.max(lag);
+                }
+
+                let index = key.index();
+                match op {
+                    TraceOp::Read => {
+                        reads += 1;
+                        if read.guard().get_and_test(&key, |value| value.index() == index).is_some() {
+                            hits += 1;
+                        }
+                    },
+                    TraceOp::Insert => {
+                        write.as_ref().unwrap().lock().unwrap().guard().insert(key, V::from_index(index));
+                    },
+                    TraceOp::Update => {
+                        write.as_ref().unwrap().lock().unwrap().guard().update(key, V::from_index(index).updated());
+                    },
+                    TraceOp::Remove => {
+                        write.as_ref().unwrap().lock().unwrap().guard().remove(key);
+                    },
+                }
+            }
+            let end = Instant::now();
+            let allocations = memory::thread_allocations() - allocations;
+
+            let execution = ExecutionWindow {
+                start,
+                end,
+                operations: worker_events.len(),
+                hits,
+                allocations,
+                idle
+            };
+            (execution, reads, total_lag, max_lag)
+        }
+    }))
+    .collect::<Vec<_>>();
+
+    let threads = join_handles.into_iter().map(|handle| handle.join().unwrap()).collect::<Vec<_>>();
 
-    let total_time = executions.iter()
-        .map(ExecutionWindow::time_elapsed)
-        .sum::<Duration>();
+    drop(write);
+
+    let total = Aggregate::of(threads.iter().map(|(execution, ..)| execution));
+    let reads = threads.iter().map(|&(_, reads, ..)| reads).sum::<usize>();
 
-    let avg_latency = total_time.as_nanos() / total_ops as u128;
+    let total_lag = threads.iter().map(|&(_, _, total_lag, _)| total_lag).sum::<Duration>();
+    let max_lag = threads.iter().map(|&(.., max_lag)| max_lag).max().unwrap_or_default();
 
-    println!("{:<18}{:<24}{}", name, throughput, avg_latency);
+    println!(
+        "{:<18}{:<24}{:<16}{:<16}{:<16}{:<16}{}",
+        name,
+        total.throughput(),
+        total.mean_latency().as_nanos(),
+        hit_ratio_of(total.hits, reads),
+        total.operations,
+        (total_lag / total.operations.max(1) as u32).as_micros(),
+        max_lag.as_micros()
+    );
 }
 
 #[derive(Clone, Copy)]
-struct ExecutionWindow {
-    start: Instant,
-    end: Instant,
-    operations: usize,
+enum ReadWorkload {
+    GetAndTest,
+    ContainsKey,
+    GetCloned,
+    GetMany(usize),
+    Fold,
 }
 
-impl ExecutionWindow {
-    pub fn time_elapsed(&self) -> Duration {
-        self.end - self.start
+impl ReadWorkload {
+    const ALL: [Self; 5] = [
+        Self::GetAndTest,
+        Self::ContainsKey,
+        Self::GetCloned,
+        Self::GetMany(16),
+        Self::Fold,
+    ];
+}
+
+impl FromStr for ReadWorkload {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, ()> {
+        match name.to_ascii_lowercase().as_str() {
+            "get_and_test" => Ok(Self::GetAndTest),
+            "contains_key" => Ok(Self::ContainsKey),
+            "get_cloned" => Ok(Self::GetCloned),
+            "get_many" => Ok(Self::GetMany(16)),
+            "fold" => Ok(Self::Fold),
+            _ => Err(()),
+        }
     }
+}
 
-    // Operations per second
-    pub fn throughput(&self) -> u64 {
-        let nanos_elapsed = self.time_elapsed().as_nanos();
-        let operations = self.operations as u128;
+impl fmt::Display for ReadWorkload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::GetAndTest => write!(f, "get_and_test"),
+            Self::ContainsKey => write!(f, "contains_key"),
+            Self::GetCloned => write!(f, "get_cloned"),
+            Self::GetMany(batch_size) => write!(f, "get_many ({} keys per batch)", batch_size),
+            Self::Fold => write!(f, "fold"),
+        }
+    }
+}
 
-        u64::try_from((operations.checked_mul(1_000_000_000).unwrap()) / nanos_elapsed).unwrap()
+// Prints each reader's throughput and latency under a benchmark's row, then how evenly the
+// readers shared the work
+fn print_readers(executions: &[ExecutionWindow], total: &Aggregate) {
+    for (i, execution) in executions.iter().enumerate() {
+        println!(
+            "{:<18}{:<24}{} ns",
+            format!("  reader {}", i),
+            format!("{} op/s", execution.throughput()),
+            execution.mean_latency().as_nanos()
+        );
     }
+    println!("{:<18}{}", "", total.fairness());
+}
+
+#[derive(Clone, Copy)]
+enum ReadLoad {
+    /// Readers look keys up as fast as they can
+    Saturating,
+    /// Readers share this many lookups per second between them
+    Paced(usize, Arrivals),
 }
 
-enum WriteOperation<K, V> {
-    Insert(K, V),
-    Update(K, V),
-    Remove(K)
+#[derive(Clone, Copy)]
+enum WriteWorkload {
+    /// 50% update, 25% remove, 25% insert
+    Mixed,
+    /// 50% upsert of present keys, 25% compare-and-swap, 25% upsert of new keys, with a single
+    /// retain over the whole map half way through
+    ReadModifyWrite,
+    /// Clears the map and inserts every entry again
+    Reload,
 }
+
+impl WriteWorkload {
+    const ALL: [Self; 3] = [
+        Self::Mixed,
+        Self::ReadModifyWrite,
+        Self::Reload,
+    ];
+}
+
+impl FromStr for WriteWorkload {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, ()> {
+        match name.to_ascii_lowercase().as_str() {
+            "mixed" => Ok(Self::Mixed),
+            "read_modify_write" => Ok(Self::ReadModifyWrite),
+            "reload" => Ok(Self::Reload),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for WriteWorkload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mixed => write!(f, "mixed"),
+            Self::ReadModifyWrite => write!(f, "read_modify_write"),
+            Self::Reload => write!(f, "reload"),
+        }
+    }
+}
+
@@ -0,0 +1,135 @@
+use std::{alloc::{GlobalAlloc, Layout, System}, cell::Cell, iter::Sum, ops::{Add, Sub}, sync::atomic::{AtomicUsize, Ordering}};
+
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+static PEAK_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+// Counted per thread so the hot path doesn't contend on a shared counter
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    static DEALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Forwards to the system allocator while keeping track of the bytes currently allocated, the
+/// high water mark of that, and how many allocations each thread has made and freed.
+pub struct CountingAllocator;
+
+impl CountingAllocator {
+    fn allocated(size: usize) {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        Self::grown(size);
+    }
+
+    fn deallocated(size: usize) {
+        DEALLOCATIONS.with(|count| count.set(count.get() + 1));
+        LIVE_BYTES.fetch_sub(size, Ordering::Relaxed);
+    }
+
+    fn grown(size: usize) {
+        let live = LIVE_BYTES.fetch_add(size, Ordering::Relaxed) + size;
+        PEAK_BYTES.fetch_max(live, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            Self::allocated(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            Self::allocated(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        Self::deallocated(layout.size());
+    }
+
+    // A reallocation counts as both an allocation and a deallocation
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            ALLOCATIONS.with(|count| count.set(count.get() + 1));
+            DEALLOCATIONS.with(|count| count.set(count.get() + 1));
+
+            if new_size >= layout.size() {
+                Self::grown(new_size - layout.size());
+            } else {
+                LIVE_BYTES.fetch_sub(layout.size() - new_size, Ordering::Relaxed);
+            }
+        }
+        new_ptr
+    }
+}
+
+pub fn live_bytes() -> usize {
+    LIVE_BYTES.load(Ordering::Relaxed)
+}
+
+pub fn peak_bytes() -> usize {
+    PEAK_BYTES.load(Ordering::Relaxed)
+}
+
+/// Starts tracking a new high water mark from the bytes allocated right now.
+pub fn reset_peak() {
+    PEAK_BYTES.store(live_bytes(), Ordering::Relaxed);
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct AllocationCount {
+    pub allocations: usize,
+    pub deallocations: usize,
+}
+
+impl Add for AllocationCount {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            allocations: self.allocations + rhs.allocations,
+            deallocations: self.deallocations + rhs.deallocations,
+        }
+    }
+}
+
+impl Sum for AllocationCount {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), Add::add)
+    }
+}
+
+impl Sub for AllocationCount {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self {
+            allocations: self.allocations - rhs.allocations,
+            deallocations: self.deallocations - rhs.deallocations,
+        }
+    }
+}
+
+/// Allocations and deallocations made by the current thread so far.
+pub fn thread_allocations() -> AllocationCount {
+    AllocationCount {
+        allocations: ALLOCATIONS.with(Cell::get),
+        deallocations: DEALLOCATIONS.with(Cell::get),
+    }
+}
+
+/// Allocations and deallocations per operation, as `allocations/deallocations`.
+pub fn per_operation(count: AllocationCount, operations: usize) -> String {
+    let operations = operations.max(1) as f64;
+    format!(
+        "{:.2}/{:.2}",
+        count.allocations as f64 / operations,
+        count.deallocations as f64 / operations
+    )
+}
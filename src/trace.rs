@@ -0,0 +1,409 @@
+//! Operation traces, recorded from any map through [`Recording`](crate::adapters::Recording) and
+//! replayed against any map. A trace file is the magic bytes `CMTRACE`, a version byte, then one
+//! record per event in time order:
+//!
+//! - the operation, one byte
+//! - the id of the thread that did it, as a LEB128 varint
+//! - nanoseconds since the previous event, as a LEB128 varint
+//! - the key's index, as a LEB128 varint
+//!
+//! Access logs from elsewhere can be turned into traces with [`import_log`].
+
+use std::{cell::Cell, collections::{HashMap, HashSet}, fs::File, io::{self, BufRead, BufReader, BufWriter, Read, Write}, path::Path, sync::Mutex, time::Duration};
+
+const MAGIC: &[u8; 7] = b"CMTRACE";
+const VERSION: u8 = 1;
+
+/// Keys in a trace are below this, so every key type and value type can be built from them.
+/// Imported keys are reduced into it.
+pub const KEY_SPACE: u64 = 1 << 48;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TraceOp {
+    Read,
+    Insert,
+    Update,
+    Remove,
+}
+
+impl TraceOp {
+    fn from_byte(byte: u8) -> io::Result<Self> {
+        match byte {
+            0 => Ok(Self::Read),
+            1 => Ok(Self::Insert),
+            2 => Ok(Self::Update),
+            3 => Ok(Self::Remove),
+            _ => Err(invalid_data("unknown trace operation")),
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::Read => 0,
+            Self::Insert => 1,
+            Self::Update => 2,
+            Self::Remove => 3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TraceEvent {
+    pub thread: u32,
+    /// Since the start of the trace
+    pub time: Duration,
+    pub op: TraceOp,
+    pub key: u64,
+}
+
+thread_local! {
+    static THREAD: Cell<u32> = const { Cell::new(0) };
+}
+
+/// Sets the thread id that operations on this thread are recorded under. Threads that don't set
+/// one are thread 0.
+pub fn set_thread(thread: u32) {
+    THREAD.with(|current| current.set(thread));
+}
+
+/// The thread id that operations on this thread are recorded under.
+pub fn current_thread() -> u32 {
+    THREAD.with(Cell::get)
+}
+
+// Events from recording handles that have been dropped
+static RECORDED: Mutex<Vec<TraceEvent>> = Mutex::new(Vec::new());
+
+/// Adds the events of a recording handle to the recorded trace.
+pub fn record(events: &mut Vec<TraceEvent>) {
+    RECORDED.lock().unwrap().append(events);
+}
+
+/// Takes every event recorded so far.
+pub fn take_recorded() -> Vec<TraceEvent> {
+    std::mem::take(&mut *RECORDED.lock().unwrap())
+}
+
+/// Writes `events` in time order, with times made relative to the first event.
+pub fn write_trace(path: impl AsRef<Path>, events: Vec<TraceEvent>) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    write_events(&mut file, events)?;
+    file.flush()
+}
+
+fn write_events(file: &mut impl Write, mut events: Vec<TraceEvent>) -> io::Result<()> {
+    events.sort_by_key(|event| event.time);
+
+    file.write_all(MAGIC)?;
+    file.write_all(&[VERSION])?;
+
+    let mut previous = events.first().map_or(Duration::ZERO, |event| event.time);
+    for event in events {
+        file.write_all(&[event.op.to_byte()])?;
+        write_varint(file, event.thread.into())?;
+        write_varint(file, (event.time - previous).as_nanos() as u64)?;
+        write_varint(file, event.key)?;
+        previous = event.time;
+    }
+
+    Ok(())
+}
+
+pub fn read_trace(path: impl AsRef<Path>) -> io::Result<Vec<TraceEvent>> {
+    read_events(BufReader::new(File::open(path)?))
+}
+
+fn read_events(mut file: impl Read) -> io::Result<Vec<TraceEvent>> {
+    let mut header = [0; 8];
+    file.read_exact(&mut header)?;
+    if &header[.. 7] != MAGIC {
+        return Err(invalid_data("not a trace file"));
+    }
+    if header[7] != VERSION {
+        return Err(invalid_data("unsupported trace version"));
+    }
+
+    let mut events = Vec::new();
+    let mut time = Duration::ZERO;
+    let mut op = [0];
+    loop {
+        match file.read_exact(&mut op) {
+            Ok(()) => (),
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(error) => return Err(error),
+        }
+
+        let op = TraceOp::from_byte(op[0])?;
+        let thread = u32::try_from(read_varint(&mut file)?)
+            .map_err(|_| invalid_data("thread id out of range"))?;
+        time += Duration::from_nanos(read_varint(&mut file)?);
+        let key = read_varint(&mut file)?;
+        if key >= KEY_SPACE {
+            return Err(invalid_data("key out of range"));
+        }
+
+        events.push(TraceEvent { thread, time, op, key });
+    }
+
+    Ok(events)
+}
+
+/// Reads a CSV or TSV access log into trace events. Each line is
+///
+/// ```text
+/// timestamp,client,op,key[,value size]
+/// ```
+///
+/// where the timestamp is in seconds and may be fractional, the client is any thread or client
+/// id, and the op is one of `read`/`get`, `insert`/`put`/`set`, `update` or `remove`/`delete`.
+/// Keys that are integers are used as they are and other keys are hashed, both reduced into
+/// [`KEY_SPACE`].
+/// Value sizes are checked but dropped, since replay always stores `u64` values. A header line,
+/// blank lines and lines starting with `#` are skipped, and lines containing a tab are split on
+/// tabs rather than commas.
+pub fn import_log(path: impl AsRef<Path>) -> io::Result<Vec<TraceEvent>> {
+    parse_log(BufReader::new(File::open(path)?))
+}
+
+fn parse_log(file: impl BufRead) -> io::Result<Vec<TraceEvent>> {
+    let mut clients = HashMap::<String, u32>::new();
+    let mut records = Vec::<(f64, u32, TraceOp, u64)>::new();
+
+    for (number, line) in file.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let invalid = |message: &str| invalid_data(&format!("line {}: {}", number + 1, message));
+
+        let separator = if line.contains('\t') { '\t' } else { ',' };
+        let fields = line.split(separator)
+            .map(|field| field.trim().trim_matches('"'))
+            .collect::<Vec<_>>();
+        if !(4 ..= 5).contains(&fields.len()) {
+            return Err(invalid("expected timestamp, client, op, key and optionally value size"));
+        }
+
+        let timestamp = match fields[0].parse::<f64>() {
+            Ok(timestamp) if timestamp.is_finite() => timestamp,
+            // A header
+            Err(_) if number == 0 => continue,
+            _ => return Err(invalid("timestamp is not a number of seconds")),
+        };
+        let next_client = clients.len() as u32;
+        let client = *clients.entry(fields[1].to_owned()).or_insert(next_client);
+        let op = match fields[2].to_ascii_lowercase().as_str() {
+            "read" | "get" => TraceOp::Read,
+            "insert" | "put" | "set" => TraceOp::Insert,
+            "update" => TraceOp::Update,
+            "remove" | "delete" => TraceOp::Remove,
+            _ => return Err(invalid("unknown op")),
+        };
+        let key = fields[3].parse::<u64>().unwrap_or_else(|_| hash_key(fields[3])) % KEY_SPACE;
+        if fields.len() == 5 && fields[4].parse::<usize>().is_err() {
+            return Err(invalid("value size is not a number of bytes"));
+        }
+
+        records.push((timestamp, client, op, key));
+    }
+
+    let first = records.iter().map(|&(timestamp, ..)| timestamp).fold(f64::INFINITY, f64::min);
+
+    Ok(records.into_iter()
+        .map(|(timestamp, thread, op, key)| TraceEvent {
+            thread,
+            time: Duration::from_secs_f64(timestamp - first),
+            op,
+            key,
+        })
+        .collect())
+}
+
+// FNV-1a, so a key always lands on the same index whichever machine imports the log
+fn hash_key(key: &str) -> u64 {
+    key.bytes().fold(0xcbf29ce484222325, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3))
+}
+
+/// Prints the op mix of a trace and how its accesses are spread over its keys.
+pub fn print_summary(events: &[TraceEvent]) {
+    const TOP_KEYS: usize = 5;
+
+    let mut ops = [0usize; 4];
+    let mut accesses = HashMap::<u64, usize>::new();
+    let mut threads = HashSet::new();
+    for event in events {
+        ops[usize::from(event.op.to_byte())] += 1;
+        *accesses.entry(event.key).or_default() += 1;
+        threads.insert(event.thread);
+    }
+
+    let mut popularity = accesses.into_iter().collect::<Vec<_>>();
+    popularity.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let total = events.len().max(1) as f64;
+    let share = |count: usize| format!("{:.1}%", count as f64 * 100.0 / total);
+    let share_of_top = |fraction: f64| {
+        let keys = ((popularity.len() as f64 * fraction).ceil() as usize).max(1);
+        share(popularity.iter().take(keys).map(|&(_, count)| count).sum())
+    };
+
+    let duration = events.iter().map(|event| event.time).max().unwrap_or_default();
+    println!(
+        "{} events from {} threads over {:.3}s, {} distinct keys",
+        events.len(),
+        threads.len(),
+        duration.as_secs_f64(),
+        popularity.len()
+    );
+    println!(
+        "Op mix: {} read, {} insert, {} update, {} remove",
+        share(ops[0]),
+        share(ops[1]),
+        share(ops[2]),
+        share(ops[3])
+    );
+    println!(
+        "Accesses to the top 1% of keys: {}, top 10%: {}",
+        share_of_top(0.01),
+        share_of_top(0.1)
+    );
+    for &(key, count) in popularity.iter().take(TOP_KEYS) {
+        println!("  key {:<22}{} accesses ({})", key, count, share(count));
+    }
+}
+
+fn write_varint(writer: &mut impl Write, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            return writer.write_all(&[byte]);
+        }
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_varint(reader: &mut impl Read) -> io::Result<u64> {
+    let mut value = 0;
+    let mut byte = [0];
+
+    for shift in (0 .. 64).step_by(7) {
+        reader.read_exact(&mut byte)?;
+        value |= u64::from(byte[0] & 0x7f) << shift;
+
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(invalid_data("varint too long"))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(log: &str) -> io::Result<Vec<TraceEvent>> {
+        parse_log(log.as_bytes())
+    }
+
+    #[test]
+    fn csv_with_header() {
+        let events = parse("ts,client,op,key,size\n10.5,web-1,GET,7,128\n10.75,web-2,set,8\n").unwrap();
+
+        assert_eq!(events, [
+            TraceEvent { thread: 0, time: Duration::ZERO, op: TraceOp::Read, key: 7 },
+            TraceEvent { thread: 1, time: Duration::from_millis(250), op: TraceOp::Insert, key: 8 },
+        ]);
+    }
+
+    #[test]
+    fn tsv_matches_csv() {
+        let csv = parse("# comment\n0,a,update,3\n\n0.001,b,delete,4,16\n").unwrap();
+        let tsv = parse("0\ta\tupdate\t3\n0.001\tb\tdelete\t4\t16\n").unwrap();
+
+        assert_eq!(csv, tsv);
+        assert_eq!(tsv[0].op, TraceOp::Update);
+        assert_eq!(tsv[1].op, TraceOp::Remove);
+    }
+
+    #[test]
+    fn keys_stay_in_key_space() {
+        let events = parse("0,a,get,user:alice\n0,a,get,\"user:alice\"\n0,a,get,user:bob\n0,a,get,18446744073709551615\n").unwrap();
+
+        assert_eq!(events[0].key, events[1].key);
+        assert_ne!(events[0].key, events[2].key);
+        assert!(events.iter().all(|event| event.key < KEY_SPACE));
+        assert_eq!(events[3].key, u64::MAX % KEY_SPACE);
+    }
+
+    #[test]
+    fn bad_lines_are_rejected() {
+        let error = parse("0,a,get,1\n1,a,frobnicate,2\n").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().starts_with("line 2"));
+
+        // Only the first line may be a header
+        assert!(parse("0,a,get,1\nts,client,op,key\n").is_err());
+        assert!(parse("0,a,get\n").is_err());
+        assert!(parse("0,a,get,1,big\n").is_err());
+    }
+
+    #[test]
+    fn varints_round_trip() {
+        for value in [0, 1, 127, 128, 300, 1 << 35, u64::MAX] {
+            let mut bytes = Vec::new();
+            write_varint(&mut bytes, value).unwrap();
+
+            assert_eq!(bytes.len(), (64 - value.leading_zeros() as usize).max(1).div_ceil(7));
+            assert_eq!(read_varint(&mut bytes.as_slice()).unwrap(), value);
+        }
+
+        assert!(read_varint(&mut [0x80; 10].as_slice()).is_err());
+        assert!(read_varint(&mut [0x80].as_slice()).is_err());
+    }
+
+    #[test]
+    fn trace_round_trip() {
+        let event = |thread, micros, op, key| TraceEvent { thread, time: Duration::from_micros(micros), op, key };
+        let events = vec![
+            event(1, 30, TraceOp::Update, KEY_SPACE - 1),
+            event(0, 10, TraceOp::Read, 5),
+            event(70_000, 20, TraceOp::Insert, 6),
+            event(0, 40, TraceOp::Remove, 5),
+        ];
+
+        let mut bytes = Vec::new();
+        write_events(&mut bytes, events.clone()).unwrap();
+        let read = read_events(bytes.as_slice()).unwrap();
+
+        // In time order, starting from the first event
+        let start = Duration::from_micros(10);
+        let mut expected = events;
+        expected.sort_by_key(|event| event.time);
+        for event in &mut expected {
+            event.time -= start;
+        }
+        assert_eq!(read, expected);
+    }
+
+    #[test]
+    fn bad_traces_are_rejected() {
+        assert!(read_events(&b"NOTRACE\x01"[..]).is_err());
+        assert!(read_events(&b"CMTRACE\x02"[..]).is_err());
+        // An unknown op, then a key past KEY_SPACE
+        assert!(read_events(&b"CMTRACE\x01\x07\x00\x00\x00"[..]).is_err());
+        let mut bytes = b"CMTRACE\x01\x00\x00\x00".to_vec();
+        write_varint(&mut bytes, KEY_SPACE).unwrap();
+        assert!(read_events(bytes.as_slice()).is_err());
+        // A record cut short
+        assert!(read_events(&b"CMTRACE\x01\x00\x00"[..]).is_err());
+    }
+}
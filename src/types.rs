@@ -0,0 +1,94 @@
+use std::{hash::Hash, sync::Arc};
+
+/// A key type the benchmarks can store. Keys are derived from an index, so every thread can build
+/// the same key without sharing the data set. Indices are below a benchmark's key space plus the
+/// keys it has inserted, or below [`KEY_SPACE`](crate::trace::KEY_SPACE) in a trace, and must stay
+/// below 2^63, since `u64` values hold twice the index.
+pub trait BenchKey: Eq + Hash + Ord + Clone + Send + Sync + 'static {
+    fn from_index(index: u64) -> Self;
+
+    fn index(&self) -> u64;
+}
+
+/// A value type the benchmarks can store. The value stored under a key is either
+/// `from_index(index)` or, once a writer has touched it, `from_index(index).updated()`, which
+/// is what readers check for.
+pub trait BenchValue: Eq + Hash + Clone + Send + Sync + 'static {
+    fn from_index(index: u64) -> Self;
+
+    fn updated(&self) -> Self;
+
+    fn index(&self) -> u64;
+}
+
+impl BenchKey for u64 {
+    fn from_index(index: u64) -> Self {
+        index
+    }
+
+    fn index(&self) -> u64 {
+        *self
+    }
+}
+
+impl BenchValue for u64 {
+    fn from_index(index: u64) -> Self {
+        debug_assert!(index < 1 << 63, "value indices must be below 2^63");
+        index * 2
+    }
+
+    fn updated(&self) -> Self {
+        *self | 1
+    }
+
+    fn index(&self) -> u64 {
+        *self / 2
+    }
+}
+
+/// A `String` key of `LEN` bytes: the index zero-padded to the left.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StringKey<const LEN: usize>(String);
+
+impl<const LEN: usize> BenchKey for StringKey<LEN> {
+    fn from_index(index: u64) -> Self {
+        assert!(LEN >= 20, "string keys must fit any u64");
+        Self(format!("{:0len$}", index, len = LEN))
+    }
+
+    fn index(&self) -> u64 {
+        self.0.parse().unwrap()
+    }
+}
+
+impl BenchKey for Arc<str> {
+    fn from_index(index: u64) -> Self {
+        Arc::from(format!("{:020}", index))
+    }
+
+    fn index(&self) -> u64 {
+        self.parse().unwrap()
+    }
+}
+
+// The `u64` value for the index in the first eight bytes, the rest filled so the whole array
+// has to be copied and compared.
+impl<const N: usize> BenchValue for [u8; N] {
+    fn from_index(index: u64) -> Self {
+        assert!(N >= 8, "byte array values must fit a u64");
+
+        let mut value = [index as u8; N];
+        value[.. 8].copy_from_slice(&<u64 as BenchValue>::from_index(index).to_le_bytes());
+        value
+    }
+
+    fn updated(&self) -> Self {
+        let mut value = *self;
+        value[0] |= 1;
+        value
+    }
+
+    fn index(&self) -> u64 {
+        BenchValue::index(&u64::from_le_bytes(self[.. 8].try_into().unwrap()))
+    }
+}
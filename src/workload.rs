@@ -0,0 +1,491 @@
+//! Workloads described in TOML files, so new scenarios don't need a recompile. A file names the
+//! maps to run, the key, value and hasher types, the initial map, the key distribution, the
+//! operation mix and the threads running it:
+//!
+//! ```toml
+//! name = "read mostly"
+//! maps = ["flashmap", "evmap", "dashmap", "flurry"]
+//! key = "u64"
+//! value = "u64"
+//! hasher = "sip"
+//! initial_size = 1_000_000
+//! key_distribution = { type = "zipf", exponent = 0.99 }
+//! hit_ratio = 0.9
+//! readers = 7
+//! writers = 1
+//! duration_ms = 1000
+//! batch_size = 1
+//!
+//! [operations]
+//! get = 90
+//! insert = 2
+//! update = 5
+//! remove = 2
+//! upsert = 1
+//! ```
+//!
+//! Readers draw from the read operations only, writers from every operation, sharing the write
+//! handle. Operation weights are relative and default to 0.
+//!
+//! A workload can also run as a sequence of phases, each overriding the duration, the write rate,
+//! the key distribution or the operation mix of the workload:
+//!
+//! ```toml
+//! [[phases]]
+//! name = "quiet"
+//! duration_ms = 2000
+//! writes_per_second = 1000
+//!
+//! [[phases]]
+//! name = "reload"
+//! duration_ms = 500
+//! operations = { get = 10, insert = 45, remove = 45 }
+//! ```
+//!
+//! The YCSB core workloads are built in as presets, see [`Ycsb`].
+
+use std::{fs, hash::BuildHasher, io, path::Path, str::FromStr, sync::Arc, time::Duration};
+
+use rand::{seq::SliceRandom, Rng};
+use rand_distr::{Distribution, Zipf};
+use serde::Deserialize;
+
+use crate::{
+    adapters::{ArcHashMap, DashMap, EvMap, FlashMap, FlurryMap, Recording},
+    hashers::{AHash, FxHash, IdentityHash, SipHash},
+    trace,
+    types::{BenchKey, BenchValue, StringKey},
+};
+
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkloadSpec {
+    pub name: String,
+    pub maps: Vec<MapKind>,
+    #[serde(default)]
+    pub key: KeyKind,
+    #[serde(default)]
+    pub value: ValueKind,
+    #[serde(default)]
+    pub hasher: HasherKind,
+    pub initial_size: usize,
+    #[serde(default)]
+    pub key_distribution: KeyDistribution,
+    /// The fraction of reads looking up a key that was in the initial map. Reads of the latest
+    /// keys always look up a key that was inserted.
+    #[serde(default = "default_hit_ratio")]
+    pub hit_ratio: f64,
+    #[serde(default)]
+    pub readers: usize,
+    #[serde(default)]
+    pub writers: usize,
+    /// How long to run for, unless the workload has phases
+    #[serde(default)]
+    pub duration_ms: u64,
+    /// Writes per second across all writers, or as many as possible if absent
+    #[serde(default)]
+    pub writes_per_second: Option<u64>,
+    /// Write operations applied through each write guard
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// Scans look up between 1 and this many keys
+    #[serde(default = "default_scan_length")]
+    pub scan_length: usize,
+    pub operations: OperationWeights,
+    #[serde(default)]
+    pub phases: Vec<PhaseSpec>,
+}
+
+/// Part of a workload. Anything left out is taken from the workload.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PhaseSpec {
+    pub name: String,
+    pub duration_ms: u64,
+    pub writes_per_second: Option<u64>,
+    pub key_distribution: Option<KeyDistribution>,
+    pub operations: Option<OperationWeights>,
+}
+
+/// A phase with the workload's settings filled in.
+pub struct Phase {
+    pub name: String,
+    pub duration: Duration,
+    pub writes_per_second: Option<u64>,
+    pub key_distribution: KeyDistribution,
+    pub operations: OperationWeights,
+}
+
+fn default_hit_ratio() -> f64 {
+    1.0
+}
+
+fn default_batch_size() -> usize {
+    1
+}
+
+fn default_scan_length() -> usize {
+    100
+}
+
+impl WorkloadSpec {
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let spec = toml::from_str::<Self>(&fs::read_to_string(path)?)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+
+        let invalid = |message| Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        if spec.maps.is_empty() {
+            return invalid("maps must name at least one map");
+        }
+        if spec.initial_size == 0 {
+            return invalid("initial_size must be at least 1");
+        }
+        if spec.readers + spec.writers == 0 {
+            return invalid("readers and writers can't both be 0");
+        }
+        if !(0.0 ..= 1.0).contains(&spec.hit_ratio) {
+            return invalid("hit_ratio must be between 0 and 1");
+        }
+        if spec.batch_size == 0 {
+            return invalid("batch_size must be at least 1");
+        }
+        if spec.scan_length == 0 {
+            return invalid("scan_length must be at least 1");
+        }
+        if spec.phases.is_empty() && spec.duration_ms == 0 {
+            return invalid("duration_ms must be set unless there are phases");
+        }
+        for phase in spec.phases() {
+            if let KeyDistribution::Zipf { exponent } | KeyDistribution::Latest { exponent } = phase.key_distribution {
+                if !(exponent >= 0.0 && exponent.is_finite()) {
+                    return invalid("key_distribution exponent must be a number of at least 0");
+                }
+            }
+            if spec.readers > 0 && phase.operations.reads().iter().all(|&(_, weight)| weight == 0) {
+                return invalid("readers need at least one read operation");
+            }
+            if spec.writers > 0 && phase.operations.all().iter().all(|&(_, weight)| weight == 0) {
+                return invalid("writers need at least one operation");
+            }
+        }
+
+        Ok(spec)
+    }
+
+    /// The phases to run, which is the whole workload if it has none.
+    pub fn phases(&self) -> Vec<Phase> {
+        if self.phases.is_empty() {
+            return vec![Phase {
+                name: "all".to_owned(),
+                duration: Duration::from_millis(self.duration_ms),
+                writes_per_second: self.writes_per_second,
+                key_distribution: self.key_distribution,
+                operations: self.operations,
+            }];
+        }
+
+        self.phases.iter()
+            .map(|phase| Phase {
+                name: phase.name.clone(),
+                duration: Duration::from_millis(phase.duration_ms),
+                writes_per_second: phase.writes_per_second.or(self.writes_per_second),
+                key_distribution: phase.key_distribution.unwrap_or(self.key_distribution),
+                operations: phase.operations.unwrap_or(self.operations),
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MapKind {
+    #[serde(rename = "arc_hashmap")]
+    ArcHashMap,
+    Flashmap,
+    Evmap,
+    Dashmap,
+    Flurry,
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyKind {
+    #[default]
+    U64,
+    String64,
+    ArcStr,
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValueKind {
+    #[default]
+    U64,
+    Bytes64,
+    Bytes1024,
+    Bytes16384,
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HasherKind {
+    #[default]
+    Sip,
+    Fx,
+    Ahash,
+    Identity,
+}
+
+/// How often each key in the map is picked. Keys missing from the map are always picked
+/// uniformly.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum KeyDistribution {
+    #[default]
+    Uniform,
+    Zipf { exponent: f64 },
+    /// Zipfian over how recently keys were inserted, so the newest keys are the most popular
+    Latest { exponent: f64 },
+}
+
+impl KeyDistribution {
+    /// Picks `count` indices from `present`.
+    pub fn sample_present<R: Rng>(self, present: &[u64], count: usize, rng: &mut R) -> Vec<u64> {
+        match self {
+            Self::Uniform => (0 .. count).map(|_| *present.choose(rng).unwrap()).collect(),
+            Self::Zipf { exponent } => {
+                let zipf = Zipf::new(present.len() as u64, exponent).unwrap();
+
+                (0 .. count)
+                    .map(|_| present[scatter(zipf.sample(rng) as u64 - 1, present.len())])
+                    .collect()
+            },
+            // Before anything is inserted, the highest indices count as the latest
+            Self::Latest { exponent } => {
+                let zipf = Zipf::new(present.len() as u64, exponent).unwrap();
+
+                (0 .. count)
+                    .map(|_| present[present.len() - zipf.sample(rng) as usize])
+                    .collect()
+            },
+        }
+    }
+}
+
+// Spreads popularity ranks over `0 .. len` so that popular keys aren't neighbours in the sorted
+// key set. Multiplying by a prime larger than any length is a bijection on `0 .. len`.
+fn scatter(rank: u64, len: usize) -> usize {
+    const PRIME: u128 = (1 << 61) - 1;
+
+    ((u128::from(rank) * PRIME) % len as u128) as usize
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Get,
+    ContainsKey,
+    GetCloned,
+    Scan,
+    Insert,
+    Update,
+    Remove,
+    Upsert,
+    CompareAndSwap,
+}
+
+impl Operation {
+    pub fn is_read(self) -> bool {
+        matches!(self, Self::Get | Self::ContainsKey | Self::GetCloned | Self::Scan)
+    }
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OperationWeights {
+    pub get: u32,
+    pub contains_key: u32,
+    pub get_cloned: u32,
+    pub scan: u32,
+    pub insert: u32,
+    pub update: u32,
+    pub remove: u32,
+    pub upsert: u32,
+    pub compare_and_swap: u32,
+}
+
+impl OperationWeights {
+    pub fn all(&self) -> [(Operation, u32); 9] {
+        [
+            (Operation::Get, self.get),
+            (Operation::ContainsKey, self.contains_key),
+            (Operation::GetCloned, self.get_cloned),
+            (Operation::Scan, self.scan),
+            (Operation::Insert, self.insert),
+            (Operation::Update, self.update),
+            (Operation::Remove, self.remove),
+            (Operation::Upsert, self.upsert),
+            (Operation::CompareAndSwap, self.compare_and_swap),
+        ]
+    }
+
+    pub fn reads(&self) -> Vec<(Operation, u32)> {
+        self.all().into_iter().filter(|&(operation, _)| operation.is_read()).collect()
+    }
+}
+
+/// The core workloads of the Yahoo! Cloud Serving Benchmark, with its default record count,
+/// Zipfian constant and scan lengths. Every client both reads and writes, except in the
+/// read-only workload C.
+#[derive(Clone, Copy)]
+pub enum Ycsb {
+    /// 50% reads, 50% updates
+    A,
+    /// 95% reads, 5% updates
+    B,
+    /// 100% reads
+    C,
+    /// 95% reads of the latest keys, 5% inserts
+    D,
+    /// 95% short scans, 5% inserts
+    E,
+    /// 50% reads, 50% read-modify-writes
+    F,
+}
+
+impl Ycsb {
+    pub const ALL: [Self; 6] = [Self::A, Self::B, Self::C, Self::D, Self::E, Self::F];
+
+    pub fn spec(self, clients: usize) -> WorkloadSpec {
+        const RECORD_COUNT: usize = 1_000_000;
+        const ZIPFIAN_CONSTANT: f64 = 0.99;
+
+        let (name, get, update, scan, insert, upsert) = match self {
+            Self::A => ("YCSB A (update heavy)", 50, 50, 0, 0, 0),
+            Self::B => ("YCSB B (read mostly)", 95, 5, 0, 0, 0),
+            Self::C => ("YCSB C (read only)", 100, 0, 0, 0, 0),
+            Self::D => ("YCSB D (read latest)", 95, 0, 0, 5, 0),
+            Self::E => ("YCSB E (short ranges)", 0, 0, 95, 5, 0),
+            Self::F => ("YCSB F (read-modify-write)", 50, 0, 0, 0, 50),
+        };
+        let (readers, writers) = match self {
+            Self::C => (clients, 0),
+            _ => (0, clients),
+        };
+        let key_distribution = match self {
+            Self::D => KeyDistribution::Latest { exponent: ZIPFIAN_CONSTANT },
+            _ => KeyDistribution::Zipf { exponent: ZIPFIAN_CONSTANT },
+        };
+
+        WorkloadSpec {
+            name: name.to_owned(),
+            maps: vec![MapKind::Flashmap, MapKind::Evmap, MapKind::Dashmap, MapKind::Flurry],
+            key: KeyKind::U64,
+            value: ValueKind::U64,
+            hasher: HasherKind::Sip,
+            initial_size: RECORD_COUNT,
+            key_distribution,
+            hit_ratio: 1.0,
+            readers,
+            writers,
+            duration_ms: 1000,
+            writes_per_second: None,
+            batch_size: 1,
+            scan_length: 100,
+            operations: OperationWeights {
+                get,
+                update,
+                scan,
+                insert,
+                upsert,
+                ..OperationWeights::default()
+            },
+            phases: Vec::new(),
+        }
+    }
+}
+
+/// Presets are named `ycsb-a` to `ycsb-f`.
+impl FromStr for Ycsb {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, ()> {
+        match name.to_ascii_lowercase().as_str() {
+            "ycsb-a" => Ok(Self::A),
+            "ycsb-b" => Ok(Self::B),
+            "ycsb-c" => Ok(Self::C),
+            "ycsb-d" => Ok(Self::D),
+            "ycsb-e" => Ok(Self::E),
+            "ycsb-f" => Ok(Self::F),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Runs the workload against its first map with every operation recorded, and writes the trace to
+/// `path`. Traces only keep key indices, so this always uses `u64` keys and values and SipHash.
+pub fn record(spec: &WorkloadSpec, path: impl AsRef<Path>) -> io::Result<()> {
+    type K = u64;
+    type V = u64;
+    type S = SipHash;
+
+    match spec.maps[0] {
+        MapKind::ArcHashMap => crate::bench_workload::<Recording<ArcHashMap<K, V, S>>, K, V, S>(spec, "Arc<HashMap>"),
+        MapKind::Flashmap => crate::bench_workload::<Recording<FlashMap>, K, V, S>(spec, "flashmap"),
+        MapKind::Evmap => crate::bench_workload::<Recording<EvMap>, K, V, S>(spec, "evmap"),
+        MapKind::Dashmap => crate::bench_workload::<Recording<DashMap<K, V, S>>, K, V, S>(spec, "dashmap"),
+        MapKind::Flurry => crate::bench_workload::<Recording<FlurryMap<K, V, S>>, K, V, S>(spec, "flurry"),
+    }
+
+    trace::write_trace(path, trace::take_recorded())
+}
+
+/// Runs `spec` on each of its maps, printing a row per map.
+pub fn run(spec: &WorkloadSpec) {
+    match spec.hasher {
+        HasherKind::Sip => with_hasher::<SipHash>(spec),
+        HasherKind::Fx => with_hasher::<FxHash>(spec),
+        HasherKind::Ahash => with_hasher::<AHash>(spec),
+        HasherKind::Identity => with_hasher::<IdentityHash>(spec),
+    }
+}
+
+fn with_hasher<S>(spec: &WorkloadSpec)
+where
+    S: BuildHasher + Default + Clone + Send + Sync + 'static,
+{
+    match spec.key {
+        KeyKind::U64 => with_key::<u64, S>(spec),
+        KeyKind::String64 => with_key::<StringKey<64>, S>(spec),
+        KeyKind::ArcStr => with_key::<Arc<str>, S>(spec),
+    }
+}
+
+fn with_key<K, S>(spec: &WorkloadSpec)
+where
+    K: BenchKey,
+    S: BuildHasher + Default + Clone + Send + Sync + 'static,
+{
+    match spec.value {
+        ValueKind::U64 => with_value::<K, u64, S>(spec),
+        ValueKind::Bytes64 => with_value::<K, [u8; 64], S>(spec),
+        ValueKind::Bytes1024 => with_value::<K, [u8; 1024], S>(spec),
+        ValueKind::Bytes16384 => with_value::<K, [u8; 16384], S>(spec),
+    }
+}
+
+fn with_value<K, V, S>(spec: &WorkloadSpec)
+where
+    K: BenchKey,
+    V: BenchValue,
+    S: BuildHasher + Default + Clone + Send + Sync + 'static,
+{
+    for &map in &spec.maps {
+        match map {
+            MapKind::ArcHashMap => crate::bench_workload::<ArcHashMap<K, V, S>, K, V, S>(spec, "Arc<HashMap>"),
+            MapKind::Flashmap => crate::bench_workload::<FlashMap, K, V, S>(spec, "flashmap"),
+            MapKind::Evmap => crate::bench_workload::<EvMap, K, V, S>(spec, "evmap"),
+            MapKind::Dashmap => crate::bench_workload::<DashMap<K, V, S>, K, V, S>(spec, "dashmap"),
+            MapKind::Flurry => crate::bench_workload::<FlurryMap<K, V, S>, K, V, S>(spec, "flurry"),
+        }
+    }
+}